@@ -0,0 +1,110 @@
+use crate::common::*;
+
+// Axis aligned bounding box, the intersection of three slabs.
+#[derive(Clone, Copy)]
+pub struct Aabb {
+    pub x: Interval,
+    pub y: Interval,
+    pub z: Interval,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Aabb::new()
+    }
+}
+
+#[allow(dead_code)]
+impl Aabb {
+    // The empty box, which contains nothing.
+    pub fn new() -> Aabb {
+        Aabb { x: Interval::empty(), y: Interval::empty(), z: Interval::empty() }
+    }
+
+    // The infinite box, for unbounded objects.
+    pub fn universe() -> Aabb {
+        Aabb { x: Interval::universe(), y: Interval::universe(), z: Interval::universe() }
+    }
+
+    pub fn from(x: Interval, y: Interval, z: Interval) -> Aabb {
+        Aabb { x, y, z }
+    }
+
+    // The box with opposite corners 'a' and 'b', in any order.
+    pub fn from_points(a: &Point3, b: &Point3) -> Aabb {
+        let slab = |a: f64, b: f64| Interval::from(a.min(b), a.max(b));
+        Aabb {
+            x: slab(a.x(), b.x()),
+            y: slab(a.y(), b.y()),
+            z: slab(a.z(), b.z()),
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            x: self.x.union(&other.x),
+            y: self.y.union(&other.y),
+            z: self.z.union(&other.z),
+        }
+    }
+
+    pub fn axis(&self, n: usize) -> &Interval {
+        match n {
+            0 => &self.x,
+            1 => &self.y,
+            _ => &self.z,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (0..3).any(|n| self.axis(n).min > self.axis(n).max)
+    }
+
+    pub fn is_finite(&self) -> bool {
+        (0..3).all(|n| self.axis(n).min.is_finite() && self.axis(n).max.is_finite())
+    }
+
+    pub fn center(&self) -> Point3 {
+        Point3::from(
+            (self.x.min + self.x.max) / 2.0,
+            (self.y.min + self.y.max) / 2.0,
+            (self.z.min + self.z.max) / 2.0,
+        )
+    }
+
+    pub fn corners(&self) -> [Point3; 8] {
+        let mut corners = [Point3::new(); 8];
+        for (index, corner) in corners.iter_mut().enumerate() {
+            *corner = Point3::from(
+                if index & 1 == 0 { self.x.min } else { self.x.max },
+                if index & 2 == 0 { self.y.min } else { self.y.max },
+                if index & 4 == 0 { self.z.min } else { self.z.max },
+            );
+        }
+        corners
+    }
+
+    // Slab test: whether the ray passes through the box within 'ray_t'.
+    pub fn hit(&self, ray: &Ray, ray_t: &Interval) -> bool {
+        let mut t_min = ray_t.min;
+        let mut t_max = ray_t.max;
+
+        for n in 0..3 {
+            let inv_d = 1.0 / ray.direction()[n];
+            let origin = ray.origin()[n];
+            let mut t0 = (self.axis(n).min - origin) * inv_d;
+            let mut t1 = (self.axis(n).max - origin) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max <= t_min {
+                return false;
+            }
+        }
+
+        true
+    }
+}
@@ -0,0 +1,165 @@
+use std::ops::{ Add, Mul, Sub };
+use std::sync::Arc;
+
+use crate::common::*;
+use crate::camera::CameraCreateInfo;
+use crate::hittable::Hittable;
+use crate::transform::{ Transform, Keyframe };
+
+// Values that can be keyframed: anything that can be blended linearly.
+pub trait Animatable: Copy + Add<Output = Self> + Sub<Output = Self> + Mul<f64, Output = Self> {}
+
+impl<T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f64, Output = T>> Animatable for T {}
+
+// How a track moves from a key to the next one.
+#[derive(Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+pub enum Interpolation {
+    Linear,
+    // Cubic Bezier with automatic handles: the curve passes smoothly through the neighbouring
+    // keys (Catmull-Rom tangents) and eases in and out of the first and last key.
+    Bezier,
+}
+
+#[derive(Clone, Copy)]
+pub struct Key<T> {
+    pub time: f64,
+    pub value: T,
+    pub interpolation: Interpolation, // Towards the next key
+}
+
+// A value over time. Before the first key and after the last one it holds still.
+#[derive(Clone)]
+pub struct Track<T> {
+    keys: Vec<Key<T>>, // Sorted by time
+}
+
+impl<T> Default for Track<T> {
+    fn default() -> Self {
+        Track { keys: Vec::new() }
+    }
+}
+
+#[allow(dead_code)]
+impl<T: Animatable> Track<T> {
+    pub fn new() -> Track<T> {
+        Track::default()
+    }
+
+    pub fn constant(value: T) -> Track<T> {
+        let mut track = Track::new();
+        track.add(0.0, value, Interpolation::Linear);
+        track
+    }
+
+    pub fn add(&mut self, time: f64, value: T, interpolation: Interpolation) {
+        let index = self.keys.partition_point(|key| key.time <= time);
+        self.keys.insert(index, Key { time, value, interpolation });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    // None if the track has no keys.
+    pub fn sample(&self, time: f64) -> Option<T> {
+        let first = self.keys.first()?;
+        let last = self.keys.last()?;
+        if time <= first.time {
+            return Some(first.value);
+        }
+        if time >= last.time {
+            return Some(last.value);
+        }
+
+        let i = self.keys.partition_point(|key| key.time <= time) - 1;
+        let (a, b) = (&self.keys[i], &self.keys[i + 1]);
+        let duration = b.time - a.time;
+        let t = (time - a.time) / duration;
+
+        Some(match a.interpolation {
+            Interpolation::Linear => a.value + (b.value - a.value) * t,
+            Interpolation::Bezier => {
+                let p0 = a.value;
+                let p3 = b.value;
+                let p1 = p0 + self.tangent(i) * (duration / 3.0);
+                let p2 = p3 - self.tangent(i + 1) * (duration / 3.0);
+
+                let s = 1.0 - t;
+                p0 * (s * s * s) + p1 * (3.0 * s * s * t) + p2 * (3.0 * s * t * t) + p3 * (t * t * t)
+            }
+        })
+    }
+
+    // Rate of change at key i, zero at the ends.
+    fn tangent(&self, i: usize) -> T {
+        let zero = self.keys[i].value * 0.0;
+        if i == 0 || i + 1 == self.keys.len() {
+            return zero;
+        }
+        let (prev, next) = (&self.keys[i - 1], &self.keys[i + 1]);
+        (next.value - prev.value) * (1.0 / (next.time - prev.time))
+    }
+}
+
+// === Camera ===
+
+// Keyframed camera settings. Empty tracks keep the value of the base 'CameraCreateInfo'.
+#[derive(Clone, Default)]
+pub struct CameraAnimation {
+    pub camera_position: Track<Vec3>,
+    pub look_at: Track<Vec3>,
+    pub vfov: Track<f64>,
+    pub focus_dist: Track<f64>,
+}
+
+impl CameraAnimation {
+    pub fn create_info(&self, base: &CameraCreateInfo, time: f64) -> CameraCreateInfo {
+        CameraCreateInfo {
+            camera_position: self.camera_position.sample(time).unwrap_or(base.camera_position),
+            look_at: self.look_at.sample(time).unwrap_or(base.look_at),
+            vfov: self.vfov.sample(time).unwrap_or(base.vfov),
+            focus_dist: self.focus_dist.sample(time).unwrap_or(base.focus_dist),
+            ..base.clone()
+        }
+    }
+}
+
+// === Objects ===
+
+// Keyframed placement of an object. Rotation is given as Euler angles in degrees
+// (see 'Mat3::from_euler'), which unlike quaternions can describe several full turns.
+// Empty tracks keep the identity.
+#[derive(Clone, Default)]
+pub struct TransformAnimation {
+    pub translation: Track<Vec3>,
+    pub rotation: Track<Vec3>,
+    pub scale: Track<Vec3>,
+}
+
+// Keyframes per shutter interval, so motion blur follows curves and fast spins.
+const SHUTTER_KEYFRAMES: usize = 8;
+
+#[allow(dead_code)]
+impl TransformAnimation {
+    pub fn keyframe(&self, time: f64) -> Keyframe {
+        let rotation = self.rotation.sample(time).unwrap_or_default();
+        Keyframe::from(
+            time,
+            self.translation.sample(time).unwrap_or_default(),
+            Quat::from_euler(rotation.x(), rotation.y(), rotation.z()),
+            self.scale.sample(time).unwrap_or(Vec3::from(1.0, 1.0, 1.0)),
+        )
+    }
+
+    // 'object' as it moves while the shutter is open.
+    pub fn transform(&self, object: Arc<dyn Hittable + Send + Sync>, shutter_open: f64, shutter_close: f64) -> Transform {
+        if shutter_close <= shutter_open {
+            return Transform::animated(object, vec![self.keyframe(shutter_open)]);
+        }
+        let keyframes = (0..=SHUTTER_KEYFRAMES)
+            .map(|k| self.keyframe(shutter_open + (shutter_close - shutter_open) * k as f64 / SHUTTER_KEYFRAMES as f64))
+            .collect();
+        Transform::animated(object, keyframes)
+    }
+}
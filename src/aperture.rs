@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use crate::common::*;
+
+// Shape of the opening of a thin lens, which out of focus highlights (bokeh) take on.
+// Samples lie in [-1, 1] x [-1, 1], x to the right and y up, and are scaled to the defocus disk.
+#[derive(Clone)]
+#[allow(dead_code)]
+pub enum Aperture {
+    Circle,
+    Polygon { blades: u32, rotation: f64 }, // Regular polygon in the unit circle, 'rotation' in degrees
+    Image(Arc<ApertureImage>),              // Grayscale mask, white lets light through
+}
+
+impl Aperture {
+    // A point on the aperture, uniform over the area that lets light through.
+    pub fn sample(&self) -> Vec3 {
+        match self {
+            Aperture::Circle => Vec3::random_in_unit_disk(),
+            Aperture::Polygon { blades, rotation } => {
+                // Pick one of the equal triangles between the centre and an edge.
+                let blades = (*blades).max(3);
+                let k = ((random_double() * blades as f64) as u32).min(blades - 1);
+                let angle = |i: u32| degrees_to_radians(*rotation) + 2.0 * PI * i as f64 / blades as f64;
+                let (a, b) = (angle(k), angle(k + 1));
+
+                let (mut s, mut t) = (random_double(), random_double());
+                if s + t > 1.0 {
+                    (s, t) = (1.0 - s, 1.0 - t);
+                }
+                Vec3::from(s * a.cos() + t * b.cos(), s * a.sin() + t * b.sin(), 0.0)
+            }
+            Aperture::Image(image) => image.sample(),
+        }
+    }
+}
+
+// An aperture mask, sampled in proportion to its brightness.
+pub struct ApertureImage {
+    width: u32,
+    height: u32,
+    cdf: Vec<f64>, // Running sum of the pixel values, row by row from the top
+}
+
+#[allow(dead_code)]
+impl ApertureImage {
+    // The longer side of the image spans [-1, 1].
+    pub fn load(path: &str) -> image::ImageResult<ApertureImage> {
+        let mask = image::open(path)?.to_luma8();
+        let mut total = 0.0;
+        let cdf = mask
+            .pixels()
+            .map(|pixel| {
+                total += pixel[0] as f64 / 255.0;
+                total
+            })
+            .collect();
+        Ok(ApertureImage { width: mask.width(), height: mask.height(), cdf })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cdf.last().map_or(true, |&total| total == 0.0)
+    }
+
+    fn sample(&self) -> Vec3 {
+        let total = self.cdf.last().copied().unwrap_or(0.0);
+        if total == 0.0 {
+            return Vec3::new();
+        }
+        let u = random_double() * total;
+        let index = self.cdf.partition_point(|&c| c <= u).min(self.cdf.len() - 1);
+        let x = (index as u32 % self.width) as f64 + random_double();
+        let y = (index as u32 / self.width) as f64 + random_double();
+
+        let half = self.width.max(self.height) as f64 / 2.0;
+        Vec3::from((x - self.width as f64 / 2.0) / half, (self.height as f64 / 2.0 - y) / half, 0.0)
+    }
+}
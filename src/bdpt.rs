@@ -0,0 +1,531 @@
+use std::sync::{Arc, Mutex};
+
+use indicatif::ProgressBar;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::common::*;
+use crate::camera::Camera;
+use crate::film::Film;
+use crate::hittable::HitRecord;
+use crate::hittable_list::HittableList;
+use crate::material;
+use crate::spectrum;
+
+// Bidirectional path tracing (Veach, chapter 10).
+// For each sample a camera subpath and a light subpath are traced, and every prefix pair
+// (s light vertices, t camera vertices) is connected. The strategies are combined with
+// the balance heuristic, so the strategy that is best for a given path dominates:
+// caustics are found by light tracing (t = 1), indirect light through small openings by
+// connecting long subpaths from both sides.
+
+// Camera subpaths carry radiance, light subpaths carry importance.
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Radiance,
+    Importance,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum VertexKind {
+    Camera,
+    Light,
+    Surface,
+}
+
+struct Vertex {
+    kind: VertexKind,
+    mode: Mode,
+    point: Point3,
+    normal: Vec3,
+    record: Option<HitRecord>, // None for the camera vertex
+    beta: Color,  // Throughput from the start of the subpath, divided by its pdf
+    pdf_fwd: f64, // Area density of sampling this vertex from its predecessor
+    pdf_rev: f64, // Area density of sampling this vertex from its successor
+    delta: bool,  // Specular vertex, cannot be connected to
+    time: f64,    // Instant of the whole path, for motion blur
+    medium: Option<Color>, // Absorbing medium the subpath arrived through, see 'material::medium_towards'
+}
+
+impl Vertex {
+    fn camera(ray: &Ray, camera: &Camera) -> Vertex {
+        Vertex {
+            kind: VertexKind::Camera,
+            mode: Mode::Radiance,
+            point: ray.origin(),
+            normal: camera.forward(),
+            record: None,
+            beta: Color::from(1.0, 1.0, 1.0),
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+            delta: false,
+            time: ray.time(),
+            medium: None,
+        }
+    }
+
+    fn light(record: HitRecord, beta: Color, pdf_pos: f64, time: f64) -> Vertex {
+        Vertex {
+            kind: VertexKind::Light,
+            mode: Mode::Importance,
+            point: record.point,
+            normal: record.normal,
+            record: Some(record),
+            beta,
+            pdf_fwd: pdf_pos,
+            pdf_rev: 0.0,
+            delta: false,
+            time,
+            medium: None,
+        }
+    }
+
+    fn surface(record: HitRecord, mode: Mode, beta: Color, pdf_fwd: f64, time: f64) -> Vertex {
+        Vertex {
+            kind: VertexKind::Surface,
+            mode,
+            point: record.point,
+            normal: record.normal,
+            record: Some(record),
+            beta,
+            pdf_fwd,
+            pdf_rev: 0.0,
+            delta: false,
+            time,
+            medium: None,
+        }
+    }
+
+    // Medium vertices have no surface, so densities and throughput carry no cosine for them.
+    fn on_surface(&self) -> bool {
+        match &self.record {
+            Some(record) => !record.material.is_volumetric(),
+            None => true,
+        }
+    }
+
+    // Absorbing medium between this vertex and 'next', for a subpath that reached it from 'prev'.
+    fn medium_towards(&self, prev: Option<&Vertex>, next: &Point3) -> Option<Color> {
+        match (&self.record, prev) {
+            (Some(record), Some(prev)) if self.kind == VertexKind::Surface => {
+                material::medium_towards(self.medium, record, &(prev.point - self.point), &(*next - self.point))
+            }
+            _ => None,
+        }
+    }
+
+    // Convert a solid angle density at this vertex into an area (or volume) density at 'next'.
+    fn convert_density(&self, pdf: f64, next: &Vertex) -> f64 {
+        let w = next.point - self.point;
+        let distance2 = w.abs2();
+        if distance2 == 0.0 {
+            return 0.0;
+        }
+        let cosine = if next.on_surface() { next.normal.dot(&w.unit()).abs() } else { 1.0 };
+        pdf * cosine / distance2
+    }
+
+    // BSDF times cosine at this vertex, for light arriving from 'prev' and leaving towards 'next'.
+    // For a light vertex, 'prev' is ignored and the emission profile towards 'next' is returned.
+    fn eval(&self, prev: Option<&Vertex>, next: &Vertex) -> Color {
+        let record = self.record.as_ref().unwrap();
+        let direction = next.point - self.point;
+
+        match self.kind {
+            VertexKind::Light => {
+                let cosine = self.normal.dot(&direction.unit());
+                if cosine > 0.0 { Color::from(cosine, cosine, cosine) } else { Color::new() }
+            }
+            _ => {
+                let prev = prev.unwrap();
+                let r_in = Ray::from(prev.point, self.point - prev.point);
+                let scattered = Ray::from(self.point, direction);
+                let f = record.material.eval(&r_in, record, &scattered);
+                match self.mode {
+                    Mode::Radiance => f,
+                    Mode::Importance => f * record.material.importance_scale(&r_in, record, &scattered),
+                }
+            }
+        }
+    }
+
+    // Area density at 'next' of continuing the subpath from this vertex, having arrived from 'prev'.
+    fn pdf(&self, camera: &Camera, prev: Option<&Vertex>, next: &Vertex) -> f64 {
+        let direction = next.point - self.point;
+        let pdf = match self.kind {
+            VertexKind::Camera => camera.pdf_direction(&direction),
+            VertexKind::Light => return self.pdf_light(next),
+            VertexKind::Surface => {
+                let record = self.record.as_ref().unwrap();
+                let prev = prev.unwrap();
+                let r_in = Ray::from(prev.point, self.point - prev.point);
+                let scattered = Ray::from(self.point, direction);
+                record.material.pdf(&r_in, record, &scattered)
+            }
+        };
+        self.convert_density(pdf, next)
+    }
+
+    // Area density at 'next' of emitting from this vertex (cosine weighted) towards 'next'.
+    fn pdf_light(&self, next: &Vertex) -> f64 {
+        let cosine = self.normal.dot(&(next.point - self.point).unit());
+        if cosine <= 0.0 {
+            return 0.0;
+        }
+        self.convert_density(cosine / PI, next)
+    }
+
+    // Radiance emitted from this vertex towards 'towards'.
+    fn emitted(&self, towards: &Vertex) -> Color {
+        match &self.record {
+            Some(record) => {
+                let r_in = Ray::from(towards.point, self.point - towards.point);
+                record.material.emitted(&r_in, record)
+            }
+            None => Color::new(),
+        }
+    }
+}
+
+pub fn render(
+    camera: &Camera,
+    world: Arc<HittableList>,
+    lights: Arc<HittableList>,
+    film: &Film,
+    progress: Arc<Mutex<ProgressBar>>,
+) {
+    let _progress = progress.clone();
+    (0..film.height()).into_par_iter().for_each(|j| {
+        for i in 0..film.width() {
+            let mut pixel_color = Color::new();
+            for _ in 0..camera.samples_per_pixel() {
+                pixel_color += sample(camera, &world, &lights, i, j, film);
+            }
+            film.add_sample(i, j, pixel_color);
+            _progress.lock().unwrap().inc(1);
+        }
+    });
+
+    progress.lock().unwrap().finish();
+}
+
+// One bidirectional sample for pixel (i, j). Light tracing contributions are splatted to 'film'.
+fn sample(camera: &Camera, world: &HittableList, lights: &HittableList, i: u32, j: u32, film: &Film) -> Color {
+    let max_depth = camera.max_depth() as usize;
+
+    let Some(ray) = camera.get_ray(i, j) else {
+        return Color::new();
+    };
+    spectrum::begin_sample(camera.spectral());
+    let (camera_path, escaped) = camera_subpath(camera, world, ray, max_depth + 1);
+    let light_path = light_subpath(world, lights, camera_path[0].time, max_depth + 1);
+
+    let mut color = escaped;
+    for t in 1..=camera_path.len() {
+        for s in 0..=light_path.len() {
+            // Light tracing needs at least one surface vertex, and s = t = 1 would see the emitter
+            // directly, which the s = 0, t = 2 strategy already does.
+            if (t == 1 && s <= 1) || s + t > max_depth + 2 {
+                continue;
+            }
+
+            if t == 1 {
+                if let Some(connection) = connect_to_camera(camera, world, &light_path, s) {
+                    let weight = mis_weight(camera, lights, &camera_path, &light_path, s, t, Some(&connection.origin));
+                    film.add_splat(connection.i, connection.j, spectrum::to_rgb(&(weight * connection.color)));
+                }
+            } else {
+                let contribution = connect(world, &camera_path, &light_path, s, t);
+                if !contribution.is_zero() {
+                    color += mis_weight(camera, lights, &camera_path, &light_path, s, t, None) * contribution;
+                }
+            }
+        }
+    }
+
+    spectrum::to_rgb(&color)
+}
+
+// === Subpaths ===
+
+// Returns the vertices and the background radiance picked up if the subpath escapes.
+fn camera_subpath(camera: &Camera, world: &HittableList, ray: Ray, max_vertices: usize) -> (Vec<Vertex>, Color) {
+    let pdf_dir = camera.pdf_direction(&ray.direction());
+
+    let mut path = vec![Vertex::camera(&ray, camera)];
+    let escaped = random_walk(world, ray, Mode::Radiance, Color::from(1.0, 1.0, 1.0), pdf_dir, max_vertices, &mut path);
+
+    let background = match escaped {
+        Some((ray, beta)) => beta * camera.background(&ray),
+        None => Color::new(),
+    };
+    (path, background)
+}
+
+// The light subpath is traced at the same instant as the camera subpath it will be joined to.
+fn light_subpath(world: &HittableList, lights: &HittableList, time: f64, max_vertices: usize) -> Vec<Vertex> {
+    let Some((record, pdf_pos)) = lights.sample_surface(time) else {
+        return Vec::new();
+    };
+    if pdf_pos == 0.0 {
+        return Vec::new();
+    }
+
+    // Emit in a cosine weighted direction about the outward normal.
+    let mut direction = record.normal + Vec3::random_unit_vector();
+    if direction.is_zero() {
+        direction = record.normal;
+    }
+    let cosine = record.normal.dot(&direction.unit());
+    let pdf_dir = cosine / PI;
+
+    let ray = Ray::from_time(record.point, direction, time);
+    let emit = record.material.emitted(&Ray::from(record.point + direction, -direction), &record);
+    if emit.is_zero() || pdf_dir <= 0.0 {
+        return Vec::new();
+    }
+
+    let mut path = vec![Vertex::light(record, emit / pdf_pos, pdf_pos, time)];
+    let beta = emit * (cosine / (pdf_pos * pdf_dir));
+    random_walk(world, ray, Mode::Importance, beta, pdf_dir, max_vertices, &mut path);
+
+    path
+}
+
+// Extend 'path' by following 'scatter'. 'pdf_dir' is the solid angle density of 'ray'.
+// Returns the escaping ray and its throughput if the walk leaves the scene.
+fn random_walk(
+    world: &HittableList,
+    ray: Ray,
+    mode: Mode,
+    beta: Color,
+    pdf_dir: f64,
+    max_vertices: usize,
+    path: &mut Vec<Vertex>,
+) -> Option<(Ray, Color)> {
+    let mut ray = ray;
+    let mut beta = beta;
+    let mut pdf_fwd = pdf_dir;
+
+    while path.len() < max_vertices {
+        let Some(record) = world.hit(&ray, &Interval::from(0.001, INFINITY)) else {
+            return Some((ray, beta));
+        };
+
+        let medium = material::arrival_medium(&ray, &record);
+        beta = beta * material::medium_transmittance(medium, record.t * ray.direction().abs());
+
+        let prev_index = path.len() - 1;
+        let mut vertex = Vertex::surface(record.clone(), mode, beta, 0.0, ray.time());
+        vertex.pdf_fwd = path[prev_index].convert_density(pdf_fwd, &vertex);
+        vertex.medium = medium;
+
+        let Some((attenuation, scattered)) = record.material.scatter(&ray, &record) else {
+            path.push(vertex);
+            break;
+        };
+
+        let pdf_rev;
+        if record.material.is_specular(&record) {
+            vertex.delta = true;
+            pdf_fwd = 0.0;
+            pdf_rev = 0.0;
+        } else {
+            pdf_fwd = record.material.pdf(&ray, &record, &scattered);
+            let reversed_in = Ray::from(record.point + scattered.direction(), -scattered.direction());
+            let reversed_out = Ray::from(record.point, -ray.direction());
+            pdf_rev = record.material.pdf(&reversed_in, &record, &reversed_out);
+        }
+        path[prev_index].pdf_rev = vertex.convert_density(pdf_rev, &path[prev_index]);
+
+        beta = beta * attenuation;
+        if mode == Mode::Importance {
+            beta *= record.material.importance_scale(&ray, &record, &scattered);
+        }
+        path.push(vertex);
+        if beta.is_zero() {
+            break;
+        }
+        ray = material::scattered_in_medium(&ray, &record, scattered);
+    }
+
+    None
+}
+
+// === Connections ===
+
+// Unweighted contribution of the path made of s light vertices and t >= 2 camera vertices.
+fn connect(world: &HittableList, camera_path: &[Vertex], light_path: &[Vertex], s: usize, t: usize) -> Color {
+    let pt = &camera_path[t - 1];
+    let pt_minus = &camera_path[t - 2];
+
+    if s == 0 {
+        // The camera subpath hit an emitter by itself.
+        return pt.beta * pt.emitted(pt_minus);
+    }
+
+    let qs = &light_path[s - 1];
+    if pt.delta || qs.delta || pt.record.is_none() {
+        return Color::new();
+    }
+    let qs_minus = if s >= 2 { Some(&light_path[s - 2]) } else { None };
+
+    let contribution = qs.beta * qs.eval(qs_minus, pt) * pt.eval(Some(pt_minus), qs) * pt.beta;
+    if contribution.is_zero() {
+        return Color::new();
+    }
+
+    let medium = pt.medium_towards(Some(pt_minus), &qs.point).or_else(|| qs.medium_towards(qs_minus, &pt.point));
+    contribution * transmittance(world, &pt.point, &qs.point, pt.time, medium) / (pt.point - qs.point).abs2()
+}
+
+struct SplatContribution {
+    origin: Point3,
+    i: u32,
+    j: u32,
+    color: Color,
+}
+
+// Light tracing strategy (t = 1): connect the light subpath prefix to a point on the lens.
+fn connect_to_camera(camera: &Camera, world: &HittableList, light_path: &[Vertex], s: usize) -> Option<SplatContribution> {
+    let qs = &light_path[s - 1];
+    if qs.delta {
+        return None;
+    }
+
+    let connection = camera.connect(&qs.point)?;
+    let lens = Vertex {
+        kind: VertexKind::Camera,
+        mode: Mode::Radiance,
+        point: connection.origin,
+        normal: Vec3::new(),
+        record: None,
+        beta: Color::from(1.0, 1.0, 1.0),
+        pdf_fwd: 0.0,
+        pdf_rev: 0.0,
+        delta: false,
+        time: qs.time,
+        medium: None,
+    };
+    let qs_minus = if s >= 2 { Some(&light_path[s - 2]) } else { None };
+
+    let color = qs.beta * qs.eval(qs_minus, &lens) * connection.importance;
+    if color.is_zero() {
+        return None;
+    }
+    let medium = qs.medium_towards(qs_minus, &connection.origin);
+    let color = color * transmittance(world, &qs.point, &connection.origin, qs.time, medium);
+    if color.is_zero() {
+        return None;
+    }
+
+    Some(SplatContribution { origin: connection.origin, i: connection.i, j: connection.j, color })
+}
+
+// Visibility between two vertices, attenuated by the media in between and by the absorbing
+// 'medium' both are in.
+fn transmittance(world: &HittableList, from: &Point3, to: &Point3, time: f64, medium: Option<Color>) -> Color {
+    let distance = (*to - *from).abs();
+    let ray = Ray::from_time(*from, (*to - *from) / distance, time);
+    world.transmittance(&ray, &Interval::from(0.001, distance - 0.001)) * material::medium_transmittance(medium, distance)
+}
+
+// === Multiple Importance Sampling ===
+
+// Balance heuristic weight of strategy (s, t), computed from the ratios of the densities
+// with which the other strategies would have generated the same path.
+fn mis_weight(
+    camera: &Camera,
+    lights: &HittableList,
+    camera_path: &[Vertex],
+    light_path: &[Vertex],
+    s: usize,
+    t: usize,
+    lens_point: Option<&Point3>,
+) -> f64 {
+    if s + t == 2 {
+        return 1.0;
+    }
+
+    // (pdf_fwd, pdf_rev, delta) of the vertices of this path, with the connection applied.
+    let mut cam: Vec<(f64, f64, bool)> = camera_path[..t].iter().map(|v| (v.pdf_fwd, v.pdf_rev, v.delta)).collect();
+    let mut light: Vec<(f64, f64, bool)> = light_path[..s].iter().map(|v| (v.pdf_fwd, v.pdf_rev, v.delta)).collect();
+
+    // The t = 1 strategy connects to a freshly sampled lens point.
+    let lens_vertex;
+    let pt = if t == 1 && lens_point.is_some() {
+        lens_vertex = Vertex {
+            kind: VertexKind::Camera,
+            mode: Mode::Radiance,
+            point: *lens_point.unwrap(),
+            normal: camera_path[0].normal,
+            record: None,
+            beta: Color::new(),
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+            delta: false,
+            time: camera_path[0].time,
+            medium: None,
+        };
+        &lens_vertex
+    } else {
+        &camera_path[t - 1]
+    };
+    let pt_minus = if t >= 2 { Some(&camera_path[t - 2]) } else { None };
+    let qs = if s >= 1 { Some(&light_path[s - 1]) } else { None };
+    let qs_minus = if s >= 2 { Some(&light_path[s - 2]) } else { None };
+
+    // Densities of sampling the connection vertices from the other side.
+    if s == 0 {
+        // pt is an emitter hit by the camera subpath.
+        let pdf_pos = lights.surface_pdf(&pt.point, &pt.normal, pt.time);
+        if pdf_pos == 0.0 {
+            return 1.0; // Not in 'lights', no other strategy could have found it
+        }
+        cam[t - 1].1 = pdf_pos;
+        if let Some(pt_minus) = pt_minus {
+            cam[t - 2].1 = pt.pdf_light(pt_minus);
+        }
+    } else {
+        let qs = qs.unwrap();
+        cam[t - 1].1 = qs.pdf(camera, qs_minus, pt);
+        if let Some(pt_minus) = pt_minus {
+            cam[t - 2].1 = pt.pdf(camera, Some(qs), pt_minus);
+        }
+        light[s - 1].1 = pt.pdf(camera, pt_minus, qs);
+        if let Some(qs_minus) = qs_minus {
+            light[s - 2].1 = qs.pdf(camera, Some(pt), qs_minus);
+        }
+    }
+    cam[t - 1].2 = false;
+    if s >= 1 {
+        light[s - 1].2 = false;
+    }
+
+    let remap0 = |f: f64| if f != 0.0 { f } else { 1.0 };
+    let mut sum_ri = 0.0;
+
+    // Strategies with fewer camera vertices.
+    let mut ri = 1.0;
+    for i in (1..t).rev() {
+        ri *= remap0(cam[i].1) / remap0(cam[i].0);
+        // Without light tracing (see 'Camera::can_connect') the t = 1 strategy is never taken.
+        if i == 1 && !camera.can_connect() {
+            continue;
+        }
+        if !cam[i].2 && !cam[i - 1].2 {
+            sum_ri += ri;
+        }
+    }
+
+    // Strategies with fewer light vertices.
+    ri = 1.0;
+    for i in (0..s).rev() {
+        ri *= remap0(light[i].1) / remap0(light[i].0);
+        let delta_prev = if i > 0 { light[i - 1].2 } else { false };
+        if !light[i].2 && !delta_prev {
+            sum_ri += ri;
+        }
+    }
+
+    1.0 / (1.0 + sum_ri)
+}
@@ -1,18 +1,25 @@
 use std::sync::{Arc, Mutex};
 
 use indicatif::ProgressBar;
-use rand::Rng;
 
 use image::RgbImage;
-use rayon::iter::ParallelIterator;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
 use crate::common::*;
+use crate::film::Film;
 use crate::hittable_list::HittableList;
+use crate::bdpt;
+use crate::photon::{ self, PhotonMappingInfo };
+use crate::mlt::{ self, MetropolisInfo };
+use crate::lens::{ LensInfo, LensSystem };
+use crate::aperture::Aperture;
+use crate::material;
+use crate::spectrum;
 
 #[allow(dead_code)]
 pub struct Camera {
     // === Hyper Parameters ===
-    image_width :      u32, // Rendered image width in pixel count
+    image_width :      u32, // Rendered image width in pixel count, per eye in stereo
     samples_per_pixel: u32, // The number of samples per pixel
     max_depth:         u32, // Maximum number of ray bounces
 
@@ -23,21 +30,97 @@ pub struct Camera {
     pixel_delta_u:     Vec3, // Offset to pixel to the right
     pixel_delta_v:     Vec3, // Offset to pixel to below
 
-    right_vec: Vec3,
-    up_vec: Vec3,
-    backward_vec: Vec3,
+    basis: Onb, // u points right, v up, w backward
 
     defocus_angle:  f64,
     focus_dist:     f64,
     defocus_disk_u: Vec3,
     defocus_disk_v: Vec3,
+    aperture:       Aperture,
+    cat_eye:        f64,
+    focus_normal:   Vec3, // Unit normal of the plane of focus, facing away from the camera
+
+    chromatic_aberration: f64,
+
+    shutter_open:  f64,
+    shutter_close: f64,
+
+    projection: Projection,
+    stereo: Option<StereoInfo>,
+    lens: Option<LensSystem>,
+    integrator: Integrator,
+    background: Option<Color>,
+    spectral: bool,
+}
+
+// How the radiance arriving at each pixel is estimated.
+// 'samples_per_pixel' counts camera samples, passes for photon mapping, or mutations per pixel for MLT.
+#[derive(Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+pub enum Integrator {
+    PathTracing,   // Unidirectional, following 'scatter' from the camera
+    Bidirectional, // Camera and light subpaths connected with MIS (see bdpt.rs)
+    PhotonMapping(PhotonMappingInfo), // Photon density estimation at the first diffuse hit (see photon.rs)
+    Metropolis(MetropolisInfo), // Primary sample space MLT over the path tracer (see mlt.rs)
+}
+
+// How directions around the camera map onto the image.
+// Only 'Perspective' uses 'vfov' and the defocus settings; the others are pinhole projections.
+#[derive(Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+pub enum Projection {
+    Perspective,                 // Thin lens
+    Orthographic { height: f64 }, // Parallel rays, the image spans 'height' world units vertically
+    FisheyeEquidistant { fov: f64 }, // r = f * theta, 'fov' degrees across the image height
+    FisheyeEquisolid { fov: f64 },   // r = 2f * sin(theta / 2), 'fov' degrees across the image height
+    Equirectangular, // Longitude across the width (360), latitude down the height (180)
+    Cylindrical,     // 360 across the width, straight verticals, square pixels at the horizon
+}
+
+// Where the two views of a stereo image go.
+#[derive(Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+pub enum StereoLayout {
+    SideBySide, // Left eye on the left, the image is twice as wide
+    TopBottom,  // Left eye on top, the image is twice as high
+}
+
+// Two views from eyes 'interocular_distance' apart, both aimed at the same point
+// 'convergence_distance' ahead: there the views agree and objects appear at screen depth.
+// Perspective views converge by shifting the image (off-axis) rather than turning the eyes.
+// Equirectangular and cylindrical views are omni-directional stereo (ODS): the eyes turn
+// with the direction of each ray, so every direction of a panorama is seen in stereo.
+#[derive(Clone, Copy, PartialEq)]
+pub struct StereoInfo {
+    pub layout: StereoLayout,
+    pub interocular_distance: f64,
+    pub convergence_distance: f64, // INFINITY for parallel views
+}
+
+impl Default for StereoInfo {
+    fn default() -> Self {
+        StereoInfo {
+            layout: StereoLayout::SideBySide,
+            interocular_distance: 0.064,
+            convergence_distance: INFINITY,
+        }
+    }
+}
+
+// A point on the lens connected to a point in the scene. See 'Camera::connect'.
+pub struct CameraConnection {
+    pub origin: Point3, // Sampled point on the lens
+    pub i: u32,         // Pixel the connection lands on
+    pub j: u32,
+    pub importance: f64, // We * cos / (pdf * distance^2), the camera side of the path throughput
 }
 
+#[derive(Clone)]
 pub struct CameraCreateInfo {
     pub samples_per_pixel: u32,
     pub max_depth: u32,
     pub aspect_ratio: f64,
-    pub image_width: u32,
+    pub image_width: u32, // Per eye in stereo
     pub vfov: f64,
 
     pub camera_position: Vec3,
@@ -46,6 +129,45 @@ pub struct CameraCreateInfo {
 
     pub defocus_angle: f64,
     pub focus_dist:    f64,
+    pub aperture:      Aperture, // Shape of the defocus disk, and so of out of focus highlights
+    // 0 to 1: the lens barrel clips the aperture towards the edges of the image, so bokeh
+    // there turns into cat's eyes. At 1 the clipping disk is off by the aperture radius in the corners.
+    // Both are for the thin lens: a lens system has its own stop and barrel, so it takes neither.
+    pub cat_eye:       f64,
+
+    // Tilt-shift, for the perspective projection. 'shift_x' and 'shift_y' move the image right and
+    // up by fractions of its width and height, so verticals stay parallel. 'tilt' and 'swing' turn
+    // the plane of focus about the horizontal and vertical axes through the focus distance, in
+    // degrees (Scheimpflug): positive tilt brings its bottom nearer, like the ground, and positive
+    // swing sends its right side farther away.
+    pub shift_x: f64,
+    pub shift_y: f64,
+    pub tilt:    f64,
+    pub swing:   f64,
+
+    // Lateral chromatic aberration, applied to the light on the film before it is tone mapped:
+    // red is magnified by 1 + this about the image centre, blue by 1 - this.
+    pub chromatic_aberration: f64,
+
+    // Rays are spread uniformly over [shutter_open, shutter_close] for motion blur.
+    // Moving objects take their time from the ray, e.g. 'Sphere::moving' goes from 0 to 1.
+    pub shutter_open:  f64,
+    pub shutter_close: f64,
+
+    pub projection: Projection,
+    pub stereo: Option<StereoInfo>, // None for a single view
+
+    // Trace through a real lens focused at 'focus_dist', in place of the perspective projection
+    // ('vfov', 'defocus_angle' and 'projection' are ignored). Scene units are metres.
+    pub lens: Option<LensInfo>,
+    pub integrator: Integrator,
+    pub background: Option<Color>, // None for the sky gradient
+
+    // Trace sampled wavelengths instead of RGB, so dispersive glass splits light into colours
+    // (see spectrum.rs). Each colour renders as in RGB, but light that reflects off several
+    // colours can differ, as spectra multiply differently than RGB triples do. Fully
+    // saturated primaries come out up to 3% desaturated. Photon mapping always renders in RGB.
+    pub spectral: bool,
 }
 
 impl Default for CameraCreateInfo {
@@ -63,6 +185,111 @@ impl Default for CameraCreateInfo {
 
             defocus_angle: 0.0,
             focus_dist: 10.0,
+            aperture: Aperture::Circle,
+            cat_eye: 0.0,
+
+            shift_x: 0.0,
+            shift_y: 0.0,
+            tilt: 0.0,
+            swing: 0.0,
+
+            chromatic_aberration: 0.0,
+
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+
+            projection: Projection::Perspective,
+            stereo: None,
+            lens: None,
+            integrator: Integrator::PathTracing,
+            background: None,
+            spectral: false,
+        }
+    }
+}
+
+// Helpers that set up the camera from the scene, e.g. for quick previews.
+#[allow(dead_code)]
+impl CameraCreateInfo {
+    // Focus on the first surface seen towards 'look_at', or on 'look_at' itself if there is none.
+    pub fn focus_on_look_at(self, world: &HittableList) -> CameraCreateInfo {
+        let forward = (self.look_at - self.camera_position).unit();
+        let ray = Ray::from_time(self.camera_position, forward, self.shutter_open);
+        let focus_dist = match world.hit(&ray, &Interval::from(0.001, INFINITY)) {
+            Some(record) => record.t,
+            None => (self.look_at - self.camera_position).abs(),
+        };
+        CameraCreateInfo { focus_dist, ..self }
+    }
+
+    // Focus on the surface seen through the centre of pixel (i, j) of a single view, or keep
+    // the focus distance if there is none.
+    pub fn focus_on_pixel(self, world: &HittableList, i: u32, j: u32) -> CameraCreateInfo {
+        // A pinhole view through the same pixels, without a lens to build.
+        let Ok(probe) = Camera::new(CameraCreateInfo {
+            defocus_angle: -1.0,
+            tilt: 0.0,
+            swing: 0.0,
+            stereo: None,
+            lens: None,
+            ..self.clone()
+        }) else {
+            return self;
+        };
+        let Some(ray) = probe.get_ray_raster(i as f64 + 0.5, j as f64 + 0.5) else {
+            return self;
+        };
+        let Some(record) = world.hit(&ray, &Interval::from(0.001, INFINITY)) else {
+            return self;
+        };
+        let focus_dist = (record.point - ray.origin()).dot(&probe.forward());
+        if focus_dist <= 0.0 {
+            return self;
+        }
+        CameraCreateInfo { focus_dist, ..self }
+    }
+
+    // Look at the centre of the world's bounding box from the current direction, from just far
+    // enough that the whole box is in view of the lens or projection, and focus there.
+    // Orthographic views are resized to fit instead. Unchanged if the world is empty or unbounded.
+    pub fn frame(self, world: &HittableList) -> CameraCreateInfo {
+        let bbox = world.bounding_box();
+        if bbox.is_empty() || !bbox.is_finite() {
+            return self;
+        }
+        let center = bbox.center();
+        let radius = (Vec3::from(bbox.x.size(), bbox.y.size(), bbox.z.size()) / 2.0).abs();
+
+        let mut backward = self.camera_position - self.look_at;
+        if backward.is_zero() {
+            backward = Vec3::from(0.0, 0.0, 1.0);
+        }
+        let backward = backward.unit();
+
+        // The bounding sphere must fit the narrower of the two fields of view. Fisheyes fit it
+        // in the image circle, up to a hemisphere.
+        let lens_vfov = self.lens.as_ref().and_then(|lens| LensSystem::vertical_fov(lens, self.aspect_ratio));
+        let half_view = match (lens_vfov, self.projection) {
+            (None, Projection::FisheyeEquidistant { fov } | Projection::FisheyeEquisolid { fov }) => {
+                degrees_to_radians(fov.min(180.0) / 2.0)
+            }
+            _ => {
+                let half_vfov = lens_vfov.unwrap_or(degrees_to_radians(self.vfov)) / 2.0;
+                half_vfov.min((half_vfov.tan() * self.aspect_ratio).atan())
+            }
+        };
+        let distance = radius.max(EPS) / half_view.sin();
+
+        let projection = match self.projection {
+            Projection::Orthographic { .. } => Projection::Orthographic { height: 2.0 * radius * (1.0 / self.aspect_ratio).max(1.0) },
+            projection => projection,
+        };
+        CameraCreateInfo {
+            camera_position: center + distance * backward,
+            look_at: center,
+            focus_dist: distance,
+            projection,
+            ..self
         }
     }
 }
@@ -70,7 +297,12 @@ impl Default for CameraCreateInfo {
 #[allow(dead_code)]
 impl Camera {
     // === Public ===
-    pub fn new(info: CameraCreateInfo) -> Camera {
+    // Fails if the lens system cannot be built, e.g. when it cannot focus at 'focus_dist', or is
+    // given thin lens settings it has no use for.
+    pub fn new(info: CameraCreateInfo) -> Result<Camera, String> {
+        if info.lens.is_some() && (!matches!(info.aperture, Aperture::Circle) || info.cat_eye > 0.0) {
+            return Err("Aperture shapes and cat's eyes are for the thin lens, a lens system has its own stop".to_string());
+        }
         
         let image_width = info.image_width;
         let image_height = std::cmp::max(1, (image_width as f64 / info.aspect_ratio) as u32);
@@ -85,28 +317,35 @@ impl Camera {
         let viewport_height = 2.0 * h * focus_dist; // MARK: ??
         let viewport_width = viewport_height * (image_width as f64 / image_height as f64);
 
-        let backward_vec = (info.camera_position - info.look_at).unit();
-        let right_vec = info.world_up.cross(&backward_vec).unit();
-        let up_vec = backward_vec.cross(&right_vec);
+        let basis = Onb::look_at(&info.camera_position, &info.look_at, &info.world_up);
 
-        // TODO: Why dot right_vec & up_vec?
-        let viewport_u = viewport_width * right_vec;
-        let viewport_v = viewport_height * -up_vec;
+        let viewport_u = viewport_width * basis.u();
+        let viewport_v = viewport_height * -basis.v();
 
         let pixel_delta_u = viewport_u / image_width as f64;
         let pixel_delta_v = viewport_v / image_height as f64;
 
-        let viewport_upper_left = position - (focus_dist * backward_vec) - viewport_u / 2.0 - viewport_v / 2.0;
+        let shift = info.shift_x * viewport_u - info.shift_y * viewport_v;
+        let viewport_upper_left = position - (focus_dist * basis.w()) - viewport_u / 2.0 - viewport_v / 2.0 + shift;
         let pixel00_loc = viewport_upper_left + 0.5 * (pixel_delta_u + pixel_delta_v);
 
         let samples_per_pixel = info.samples_per_pixel;
         let max_depth = info.max_depth;
 
         let defocus_radius = focus_dist * degrees_to_radians(defocus_angle / 2.0).tan();
-        let defocus_disk_u = right_vec * defocus_radius;
-        let defocus_disk_v = up_vec * defocus_radius;
+        let defocus_disk_u = basis.u() * defocus_radius;
+        let defocus_disk_v = basis.v() * defocus_radius;
+
+        let focus_normal = (-basis.w()
+            - degrees_to_radians(info.swing).tan() * basis.u()
+            - degrees_to_radians(info.tilt).tan() * basis.v()).unit();
 
-        Camera {
+        let lens = match &info.lens {
+            Some(lens) => Some(LensSystem::new(lens, image_width as f64 / image_height as f64, focus_dist)?),
+            None => None,
+        };
+
+        Ok(Camera {
             // aspect_ratio,
             image_width,
             image_height,
@@ -117,56 +356,283 @@ impl Camera {
             samples_per_pixel,
             max_depth,
 
-            backward_vec,
-            right_vec,
-            up_vec,
+            basis,
 
             focus_dist,
             defocus_angle,
             defocus_disk_u,
             defocus_disk_v,
+            aperture: info.aperture,
+            cat_eye: info.cat_eye,
+            focus_normal,
+
+            chromatic_aberration: info.chromatic_aberration,
+
+            shutter_open: info.shutter_open,
+            shutter_close: info.shutter_close,
+
+            projection: info.projection,
+            stereo: info.stereo,
+            lens,
+            integrator: info.integrator,
+            background: info.background,
+            spectral: info.spectral,
+        })
+    }
+
+    pub fn render(
+        &self,
+        world: Arc<HittableList>,
+        lights: Arc<HittableList>,
+        img: &mut RgbImage,
+        progress: Arc<Mutex<ProgressBar>>,
+    ) {
+        let film = Film::new(self.image_width(), self.image_height());
+        match self.integrator {
+            Integrator::PathTracing => self.render_path_tracing(world, &film, progress),
+            Integrator::Bidirectional => bdpt::render(self, world, lights, &film, progress),
+            Integrator::PhotonMapping(info) => photon::render(self, world, lights, &film, progress, &info),
+            Integrator::Metropolis(info) => mlt::render(self, world, &film, progress, &info),
+        }
+
+        // Lens effects apply to the light on the film, before it is tone mapped and quantised.
+        let colors = self.apply_chromatic_aberration(film.colors(self.samples_per_pixel));
+        for (i, j, pixel) in img.enumerate_pixels_mut() {
+            *pixel = image::Rgb(transform_color(colors[(j * film.width() + i) as usize], 1));
+        }
+    }
+
+    // Size of the rendered image, with both views in stereo.
+    pub fn image_height(&self) -> u32 {
+        match self.stereo {
+            Some(StereoInfo { layout: StereoLayout::TopBottom, .. }) => 2 * self.image_height,
+            _ => self.image_height,
+        }
+    }
+    pub fn image_width(&self) -> u32 {
+        match self.stereo {
+            Some(StereoInfo { layout: StereoLayout::SideBySide, .. }) => 2 * self.image_width,
+            _ => self.image_width,
+        }
+    }
+    pub fn samples_per_pixel(&self) -> u32 {
+        self.samples_per_pixel
+    }
+    pub fn max_depth(&self) -> u32 {
+        self.max_depth
+    }
+    pub fn spectral(&self) -> bool {
+        self.spectral
+    }
+    pub fn forward(&self) -> Vec3 {
+        -self.basis.w()
+    }
+
+    // Radiance arriving along a ray that escapes the scene.
+    pub fn background(&self, ray: &Ray) -> Color {
+        if let Some(background) = self.background {
+            return spectrum::upsample(&background);
+        }
+
+        let unit_direction = ray.direction().unit();
+        let a = 0.5 * (unit_direction.y() + 1.0);
+        spectrum::upsample(&((1.0 - a) * Color::from(1.0, 1.0, 1.0) + a * Color::from(0.5, 0.7, 1.0)))
+    }
+
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    // Whether light paths can be connected to the camera with 'connect' (and 'pdf_direction' is
+    // meaningful). Only a single perspective view supports it; BDPT skips light tracing otherwise.
+    pub fn can_connect(&self) -> bool {
+        self.projection == Projection::Perspective && self.stereo.is_none() && self.lens.is_none()
+    }
+
+    // Get a randomly sampled camera ray for the pixel at location (i, j).
+    // None where the projection does not cover the image, e.g. outside a fisheye's image circle.
+    pub fn get_ray(&self, i: u32, j: u32) -> Option<Ray> {
+        self.get_ray_raster(i as f64 + random_double(), j as f64 + random_double())
+    }
+
+    // Get a camera ray through raster position (x, y). Pixel (i, j) covers [i, i+1) x [j, j+1).
+    pub fn get_ray_raster(&self, x: f64, y: f64) -> Option<Ray> {
+        let time = self.sample_time();
+        let (x, y, eye) = self.view_raster(x, y);
+        let width = self.image_width as f64;
+        let height = self.image_height as f64;
+
+        if let Some(lens) = &self.lens {
+            let (origin, direction, weight) = lens.generate_ray(x / width, y / height)?;
+            // Keeping rays in proportion to their weight darkens the image where the lens
+            // vignettes, while every ray that is traced carries full radiance.
+            if random_double() >= weight {
+                return None;
+            }
+            // Lens space looks along +z.
+            let to_world = |v: &Vec3| self.basis.local(&Vec3::from(v.x(), v.y(), -v.z()));
+            let mut origin = self.position + to_world(&origin);
+            if let Some(stereo) = self.stereo {
+                origin += self.eye_offset(&stereo, &self.forward(), eye);
+            }
+            return Some(Ray::from_time(origin, to_world(&direction), time));
+        }
+
+        let direction = match self.projection {
+            Projection::Perspective => {
+                let pixel_sample = self.pixel00_loc + ((x - 0.5) * self.pixel_delta_u) + ((y - 0.5) * self.pixel_delta_v);
+                let mut ray_origin = self.position;
+                if self.defocus_angle > 0.0 {
+                    let p = self.aperture.sample();
+                    if self.cat_eye_clips(&p, x, y) {
+                        return None;
+                    }
+                    ray_origin = self.lens_point(&p);
+                }
+                // The lens focuses on the point of the plane of focus seen through the pixel.
+                let to_pixel = pixel_sample - self.position;
+                let cos_focus = to_pixel.dot(&self.focus_normal);
+                let (mut target, mut direction) = if cos_focus > 0.0 {
+                    let focus_point = self.position + (self.focus_dist * self.forward().dot(&self.focus_normal) / cos_focus) * to_pixel;
+                    (Some(focus_point), Vec3::new())
+                } else {
+                    (None, to_pixel) // The plane of focus is not in view here: focus at infinity
+                };
+                if let Some(stereo) = self.stereo {
+                    // Shift the image so both views meet at the convergence distance.
+                    let offset = self.eye_offset(&stereo, &self.forward(), eye);
+                    target = target.map(|point| point + offset * (1.0 - self.focus_dist / stereo.convergence_distance));
+                    ray_origin += offset;
+                }
+                if let Some(target) = target {
+                    direction = target - ray_origin;
+                }
+                return Some(Ray::from_time(ray_origin, direction, time));
+            }
+            Projection::Orthographic { height: view_height } => {
+                let view_width = view_height * width / height;
+                let origin = self.position
+                    + (x / width - 0.5) * view_width * self.basis.u()
+                    + (0.5 - y / height) * view_height * self.basis.v();
+                return Some(self.eye_ray(origin, -self.basis.w(), eye, time));
+            }
+            Projection::FisheyeEquidistant { fov } | Projection::FisheyeEquisolid { fov } => {
+                // Offset from the image centre, 1 at the top edge.
+                let dx = (x - width / 2.0) / (height / 2.0);
+                let dy = (height / 2.0 - y) / (height / 2.0);
+                let r = (dx * dx + dy * dy).sqrt();
+                if r > 1.0 {
+                    return None; // Outside the image circle
+                }
+                let theta_max = degrees_to_radians(fov.min(360.0) / 2.0);
+                let theta = match self.projection {
+                    Projection::FisheyeEquidistant { .. } => r * theta_max,
+                    _ => 2.0 * (r * (theta_max / 2.0).sin()).asin(),
+                };
+                let phi = dy.atan2(dx);
+                Vec3::from(theta.sin() * phi.cos(), theta.sin() * phi.sin(), -theta.cos())
+            }
+            Projection::Equirectangular => {
+                let phi = (x / width - 0.5) * 2.0 * PI;
+                let theta = (0.5 - y / height) * PI;
+                Vec3::from(theta.cos() * phi.sin(), theta.sin(), -theta.cos() * phi.cos())
+            }
+            Projection::Cylindrical => {
+                let phi = (x / width - 0.5) * 2.0 * PI;
+                let up = (0.5 - y / height) * 2.0 * PI * height / width;
+                Vec3::from(phi.sin(), up, -phi.cos())
+            }
+        };
+
+        // 'direction' is in camera space: x right, y up, -z forward.
+        Some(self.eye_ray(self.position, self.basis.local(&direction), eye, time))
+    }
+
+    // A random instant while the shutter is open.
+    pub fn sample_time(&self) -> f64 {
+        self.shutter_open + random_double() * (self.shutter_close - self.shutter_open)
+    }
+
+    // Solid angle density with which 'get_ray' (of a uniformly chosen pixel) produces 'direction'.
+    // Zero unless 'can_connect'.
+    pub fn pdf_direction(&self, direction: &Vec3) -> f64 {
+        if !self.can_connect() {
+            return 0.0;
+        }
+        match self.film_density(&self.position, &direction.unit()) {
+            Some((_, _, density)) => density,
+            None => 0.0,
         }
     }
 
-    pub fn render(&self, world: Arc<HittableList>, img: &mut RgbImage, progress: Arc<Mutex<ProgressBar>>) {
+    // Sample a point on the lens and connect it to 'point'.
+    // Returns None if 'point' is not visible through the image, or the projection does not support it.
+    pub fn connect(&self, point: &Point3) -> Option<CameraConnection> {
+        if !self.can_connect() {
+            return None;
+        }
+        let p = self.aperture.sample();
+        let origin = if self.defocus_angle <= 0.0 { self.position } else { self.lens_point(&p) };
+        let to_point = *point - origin;
+        let distance2 = to_point.abs2();
+        let direction = to_point.unit();
+
+        let cos_theta = direction.dot(&-self.basis.w());
+        if cos_theta <= 0.0 {
+            return None;
+        }
+        let (x, y, density) = self.film_density(&origin, &direction)?;
+        if self.defocus_angle > 0.0 && self.cat_eye_clips(&p, x, y) {
+            return None;
+        }
+        let (i, j) = (x as u32, y as u32);
+
+        // We = density / (lens_area * cos), and the lens point is drawn with density 1 / lens_area.
+        let importance = density / distance2;
+
+        Some(CameraConnection { origin, i, j, importance })
+    }
+
+    // === Private ===
+    fn render_path_tracing(&self, world: Arc<HittableList>, film: &Film, progress: Arc<Mutex<ProgressBar>>) {
 
         // ppm_header(out, self.image_width, self.image_height);
 
         let _world = world.clone();
         let _progress = progress.clone();
-        img.par_enumerate_pixels_mut().for_each(move |(i, j, pixel)| {
-            let mut pixel_color = Color::from(0.0, 0.0, 0.0);
-            for _ in 0..self.samples_per_pixel {
-                let ray = self.get_ray(i, j);
-                let _world = _world.clone();
-                pixel_color += Camera::ray_color(&ray, self.max_depth, _world);
+        (0..film.height()).into_par_iter().for_each(move |j| {
+            for i in 0..film.width() {
+                let mut pixel_color = Color::from(0.0, 0.0, 0.0);
+                for _ in 0..self.samples_per_pixel {
+                    let Some(ray) = self.get_ray(i, j) else {
+                        continue;
+                    };
+                    let _world = _world.clone();
+                    spectrum::begin_sample(self.spectral);
+                    pixel_color += spectrum::to_rgb(&self.ray_color(&ray, self.max_depth, _world));
+                }
+                // write_color(out, pixel_color, self.samples_per_pixel);
+                film.add_sample(i, j, pixel_color);
+                _progress.lock().unwrap().inc(1);
             }
-            // write_color(out, pixel_color, self.samples_per_pixel);
-            *pixel = image::Rgb(transform_color(pixel_color, self.samples_per_pixel));
-            _progress.lock().unwrap().inc(1);
         });
 
         progress.lock().unwrap().finish();
     }
 
-    pub fn image_height(&self) -> u32 {
-        self.image_height
-    }
-    pub fn image_width(&self) -> u32 {
-        self.image_width
-    }
-
-    // === Private ===
-    fn ray_color<W: AsRef<HittableList>>(ray: &Ray, depth: u32, world: W) -> Color {
-        if depth <= 0 {
+    pub fn ray_color<W: AsRef<HittableList>>(&self, ray: &Ray, depth: u32, world: W) -> Color {
+        if depth == 0 {
             return Color::from(0.0, 0.0, 0.0);
         }
 
         if let Some(x) = world.as_ref().hit(ray, &Interval::from(0.001, INFINITY)) {
+            let transmittance = material::arrival_transmittance(ray, &x);
+            let emitted = x.material.emitted(ray, &x);
             if let Some((attenuation, scattered)) = x.material.scatter(ray, &x) {
-                return attenuation * Camera::ray_color(&scattered, depth-1, world);
+                let scattered = material::scattered_in_medium(ray, &x, scattered);
+                return transmittance * (emitted + attenuation * self.ray_color(&scattered, depth-1, world));
             } else {
-                return Color::from(0.0, 0.0, 0.0);
+                return transmittance * emitted;
             }
 
             // let direction = x.normal + Vec3::random_on_hemisphere(x.normal);
@@ -174,32 +640,151 @@ impl Camera {
             // return 0.5 * Camera::ray_color(&Ray::from(x.point, direction), depth - 1, world);
         }
 
-        let unit_direction = ray.direction().unit();
-        let a = 0.5 * (unit_direction.y() + 1.0);
-        (1.0 - a) * Color::from(1.0, 1.0, 1.0) + a * Color::from(0.5, 0.7, 1.0)
+        self.background(ray)
     }
 
-    // Get a randomly sampled camera ray for the pixel at location (i, j).
-    fn get_ray(&self, i: u32, j: u32) -> Ray {
-        let pixel_center = self.pixel00_loc + (i as f64 * self.pixel_delta_u) + (j as f64 * self.pixel_delta_v);
-        let pixel_sample = pixel_center + self.pixel_sample_square();
+    // Area of the image plane at unit distance in front of the camera.
+    fn film_area(&self) -> f64 {
+        let width = self.pixel_delta_u.abs() * self.image_width as f64;
+        let height = self.pixel_delta_v.abs() * self.image_height as f64;
+        width * height / (self.focus_dist * self.focus_dist)
+    }
 
-        let ray_origin = if self.defocus_angle < 0.0 { self.position } else { self.defocus_disk_sample() };
-        let ray_direction = pixel_sample - ray_origin;
+    // Raster position hit by a ray leaving the lens at 'origin' along unit 'direction', and the
+    // solid angle density with which 'get_ray' from that lens point (of a uniformly chosen pixel)
+    // produces 'direction'. The ray meets the plane of focus at a point, which the image plane
+    // sees through the centre of the lens.
+    fn film_density(&self, origin: &Point3, direction: &Vec3) -> Option<(f64, f64, f64)> {
+        let cos_focus = direction.dot(&self.focus_normal);
+        if cos_focus <= 0.0 {
+            return None;
+        }
+        let focus_center = self.position + self.focus_dist * self.forward();
+        let distance = (focus_center - *origin).dot(&self.focus_normal) / cos_focus;
+        if distance <= 0.0 {
+            return None;
+        }
+        let to_focus = *origin + distance * *direction - self.position;
+        let center_distance = to_focus.abs();
+        let to_focus = to_focus / center_distance;
+        let cos_theta = to_focus.dot(&self.forward());
+        if cos_theta <= 0.0 {
+            return None;
+        }
+        let image_point = self.position + (self.focus_dist / cos_theta) * to_focus;
+
+        let upper_left = self.pixel00_loc - 0.5 * (self.pixel_delta_u + self.pixel_delta_v);
+        let offset = image_point - upper_left;
+        let x = offset.dot(&self.pixel_delta_u) / self.pixel_delta_u.abs2();
+        let y = offset.dot(&self.pixel_delta_v) / self.pixel_delta_v.abs2();
+        if x < 0.0 || y < 0.0 || x >= self.image_width as f64 || y >= self.image_height as f64 {
+            return None;
+        }
 
-        Ray::from(ray_origin, ray_direction)
+        // Film area per solid angle seen from the centre, times the area of the plane of focus
+        // per solid angle seen from 'origin'. Without tilt this is 1 / (A * cos^3).
+        let density = to_focus.dot(&self.focus_normal).abs() * distance * distance
+            / (center_distance * center_distance * cos_theta.powi(3) * cos_focus * self.film_area());
+        Some((x, y, density))
     }
 
-    // Returns a random point in the camera defocus disk.
-    fn defocus_disk_sample(&self) -> Vec3 {
-        let p = Vec3::random_in_unit_disk();
+    // Raster position within the view of one eye, and the eye: -1 left, 1 right, 0 without stereo.
+    fn view_raster(&self, x: f64, y: f64) -> (f64, f64, f64) {
+        let width = self.image_width as f64;
+        let height = self.image_height as f64;
+        match self.stereo.map(|stereo| stereo.layout) {
+            None => (x, y, 0.0),
+            Some(StereoLayout::SideBySide) if x < width => (x, y, -1.0),
+            Some(StereoLayout::SideBySide) => (x - width, y, 1.0),
+            Some(StereoLayout::TopBottom) if y < height => (x, y, -1.0),
+            Some(StereoLayout::TopBottom) => (x, y - height, 1.0),
+        }
+    }
+
+    // Position of 'eye' relative to the camera when looking along 'direction'.
+    // Panoramic views place the eyes to the side of the direction (ODS). The separation fades
+    // towards the poles, where looking straight up or down leaves no left and right.
+    fn eye_offset(&self, stereo: &StereoInfo, direction: &Vec3, eye: f64) -> Vec3 {
+        let right = match self.projection {
+            Projection::Equirectangular | Projection::Cylindrical => direction.unit().cross(&self.basis.v()),
+            _ => self.basis.u(),
+        };
+        eye * stereo.interocular_distance / 2.0 * right
+    }
+
+    // The ray from 'origin' along 'direction' as seen by 'eye'.
+    fn eye_ray(&self, origin: Point3, direction: Vec3, eye: f64, time: f64) -> Ray {
+        let Some(stereo) = self.stereo else {
+            return Ray::from_time(origin, direction, time);
+        };
+        let offset = self.eye_offset(&stereo, &direction, eye);
+        let direction = if stereo.convergence_distance.is_finite() {
+            stereo.convergence_distance * direction.unit() - offset
+        } else {
+            direction
+        };
+        Ray::from_time(origin + offset, direction, time)
+    }
+
+    // The point of the defocus disk at aperture sample 'p'.
+    fn lens_point(&self, p: &Vec3) -> Point3 {
         self.position + p[0] * self.defocus_disk_u + p[1] * self.defocus_disk_v
     }
 
-    // Generate a random point in the square surrounding a pixel at the origin.
-    fn pixel_sample_square(&self) -> Vec3 {
-        let px = -0.5 + rand::thread_rng().gen_range(0.0..1.0);
-        let py = -0.5 + rand::thread_rng().gen_range(0.0..1.0);
-        return (px * self.pixel_delta_u) + (py * self.pixel_delta_v);
+    // Whether the lens barrel blocks aperture sample 'p' as seen from raster position (x, y)
+    // of a view: 'p' must also lie in a unit disk moved towards the image centre.
+    fn cat_eye_clips(&self, p: &Vec3, x: f64, y: f64) -> bool {
+        if self.cat_eye <= 0.0 {
+            return false;
+        }
+        let width = self.image_width as f64;
+        let height = self.image_height as f64;
+        let half_diagonal = (width * width + height * height).sqrt() / 2.0;
+        let cx = (x - width / 2.0) / half_diagonal;
+        let cy = (height / 2.0 - y) / half_diagonal;
+        let (dx, dy) = (p.x() + self.cat_eye * cx, p.y() + self.cat_eye * cy);
+        dx * dx + dy * dy > 1.0
     }
+
+    // 'colors' of the film with the red and blue channels of each view magnified about its centre.
+    fn apply_chromatic_aberration(&self, colors: Vec<Color>) -> Vec<Color> {
+        if self.chromatic_aberration == 0.0 {
+            return colors;
+        }
+        let (film_width, film_height) = (self.image_width(), self.image_height());
+        let width = self.image_width as f64;
+        let height = self.image_height as f64;
+
+        let mut aberrated = Vec::with_capacity(colors.len());
+        for j in 0..film_height {
+            for i in 0..film_width {
+                let (x, y, _) = self.view_raster(i as f64 + 0.5, j as f64 + 0.5);
+                let (left, top) = (i as f64 + 0.5 - x, j as f64 + 0.5 - y);
+                // A channel magnified by 'scale' shows here what lies 1 / scale as far from the centre.
+                let magnified = |channel: usize, scale: f64| {
+                    let sx = (width / 2.0 + (x - width / 2.0) / scale).clamp(0.5, width - 0.5);
+                    let sy = (height / 2.0 + (y - height / 2.0) / scale).clamp(0.5, height - 0.5);
+                    bilinear(&colors, film_width, film_height, left + sx - 0.5, top + sy - 0.5, channel)
+                };
+                aberrated.push(Color::from(
+                    magnified(0, 1.0 + self.chromatic_aberration),
+                    colors[(j * film_width + i) as usize].y(),
+                    magnified(2, 1.0 - self.chromatic_aberration),
+                ));
+            }
+        }
+        aberrated
+    }
+}
+
+// Channel 'channel' of the 'width' by 'height' 'colors' interpolated at (x, y), with pixel
+// centres at integers.
+fn bilinear(colors: &[Color], width: u32, height: u32, x: f64, y: f64, channel: usize) -> f64 {
+    let (x0, y0) = (x.floor().max(0.0) as u32, y.floor().max(0.0) as u32);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+    let value = |i: u32, j: u32| colors[(j * width + i) as usize][channel];
+    let top = value(x0, y0) * (1.0 - fx) + value(x1, y0) * fx;
+    let bottom = value(x0, y1) * (1.0 - fx) + value(x1, y1) * fx;
+    top * (1.0 - fy) + bottom * fy
 }
@@ -8,6 +8,11 @@ pub fn ppm_header(out: &mut dyn Write, width: u32, height: u32) {
     writeln!(out, "P3\n{} {}\n255", width, height).expect("Cannot write to file");
 }
 
+#[inline]
+pub fn luminance(color: Color) -> f64 {
+    0.2126 * color.x() + 0.7152 * color.y() + 0.0722 * color.z()
+}
+
 #[inline]
 pub fn linear_to_gamma(linear_component: f64) -> f64 {
     linear_component.sqrt()
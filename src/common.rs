@@ -2,6 +2,11 @@ pub use crate::color::*;
 pub use crate::interval::*;
 pub use crate::ray::*;
 pub use crate::vec3::*;
+pub use crate::mat3::*;
+pub use crate::mat4::*;
+pub use crate::quat::*;
+pub use crate::onb::*;
+pub use crate::sampler::{ random_double, random_range };
 pub use crate::common::consts::*;
 
 mod consts {
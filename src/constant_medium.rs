@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use crate::common::*;
+use crate::hittable::{ Hittable, HitRecord };
+use crate::material::{ Material, Isotropic };
+use crate::aabb::Aabb;
+
+// A volume of constant density bounded by a closed hittable (fog, smoke).
+// A ray travelling a distance d inside it scatters with probability 1 - exp(-density * d),
+// at a point returned as a hit whose material is the phase function.
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hittable + Send + Sync>,
+    neg_inv_density: f64,
+    phase_function: Arc<dyn Material + Send + Sync>,
+}
+
+#[allow(dead_code)]
+impl ConstantMedium {
+    pub fn from(
+        boundary: Arc<dyn Hittable + Send + Sync>,
+        density: f64,
+        albedo: &Color,
+    ) -> ConstantMedium {
+        ConstantMedium::with_phase_function(boundary, density, Arc::new(Isotropic::from(albedo)))
+    }
+
+    pub fn with_phase_function(
+        boundary: Arc<dyn Hittable + Send + Sync>,
+        density: f64,
+        phase_function: Arc<dyn Material + Send + Sync>,
+    ) -> ConstantMedium {
+        ConstantMedium {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function,
+        }
+    }
+}
+
+// Where the ray enters and leaves the boundary within 'ray_t', even if it starts inside.
+pub fn boundary_segment(boundary: &(dyn Hittable + Send + Sync), ray: &Ray, ray_t: &Interval) -> Option<(f64, f64)> {
+    let rec1 = boundary.hit(ray, &Interval::universe())?;
+    let rec2 = boundary.hit(ray, &Interval::from(rec1.t + 0.0001, INFINITY))?;
+
+    let t_enter = rec1.t.max(ray_t.min).max(0.0);
+    let t_exit = rec2.t.min(ray_t.max);
+    if t_enter >= t_exit {
+        return None;
+    }
+    Some((t_enter, t_exit))
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, ray: &Ray, ray_t: &Interval) -> Option<HitRecord> {
+        let (t_enter, t_exit) = boundary_segment(self.boundary.as_ref(), ray, ray_t)?;
+
+        let ray_length = ray.direction().abs();
+        let distance_inside_boundary = (t_exit - t_enter) * ray_length;
+        let hit_distance = self.neg_inv_density * (1.0 - random_double()).ln();
+        if hit_distance > distance_inside_boundary {
+            return None;
+        }
+
+        let t = t_enter + hit_distance / ray_length;
+        Some(HitRecord {
+            point: ray.at(t),
+            normal: Vec3::from(1.0, 0.0, 0.0), // Arbitrary, see 'Material::is_volumetric'
+            front_face: true,
+            t,
+            u: 0.0,
+            v: 0.0,
+            material: Arc::clone(&self.phase_function),
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+
+    fn transmittance(&self, ray: &Ray, ray_t: &Interval) -> Color {
+        match boundary_segment(self.boundary.as_ref(), ray, ray_t) {
+            Some((t_enter, t_exit)) => {
+                let transmittance = ((t_exit - t_enter) * ray.direction().abs() / self.neg_inv_density).exp();
+                Color::from(transmittance, transmittance, transmittance)
+            }
+            None => Color::from(1.0, 1.0, 1.0),
+        }
+    }
+}
+
@@ -0,0 +1,144 @@
+use std::fs::File;
+use std::io::{ self, BufRead, BufReader, Read };
+
+use crate::common::*;
+use crate::perlin::Perlin;
+
+// A scalar field over space, used for the density and emission of heterogeneous media.
+pub trait DensityField {
+    fn density(&self, point: &Point3) -> f64;
+
+    // Upper bound of 'density' everywhere, the majorant for delta and ratio tracking.
+    fn max_density(&self) -> f64;
+}
+
+// === Procedural Noise ===
+
+// Turbulent Perlin noise clamped to [0, 1], for clouds and smoke.
+pub struct NoiseDensity {
+    noise: Perlin,
+    scale: f64,
+    octaves: u32,
+}
+
+impl NoiseDensity {
+    pub fn from(scale: f64, octaves: u32) -> NoiseDensity {
+        NoiseDensity {
+            noise: Perlin::new(),
+            scale,
+            octaves,
+        }
+    }
+}
+
+impl DensityField for NoiseDensity {
+    fn density(&self, point: &Point3) -> f64 {
+        self.noise.turb(&(self.scale * *point), self.octaves).clamp(0.0, 1.0)
+    }
+
+    fn max_density(&self) -> f64 {
+        1.0
+    }
+}
+
+// === Voxel Grid ===
+
+// Densities on a regular grid spanning the box [min, max], interpolated trilinearly.
+// Outside the box the density is zero.
+pub struct VoxelGrid {
+    nx: usize,
+    ny: usize,
+    nz: usize,
+    data: Vec<f64>, // x varies fastest, then y, then z
+    min: Point3,
+    max: Point3,
+    max_density: f64,
+}
+
+#[allow(dead_code)]
+impl VoxelGrid {
+    pub fn from(nx: usize, ny: usize, nz: usize, data: Vec<f64>, min: Point3, max: Point3) -> VoxelGrid {
+        assert_eq!(data.len(), nx * ny * nz, "Voxel count does not match the grid resolution");
+        let max_density = data.iter().cloned().fold(0.0, f64::max);
+        VoxelGrid { nx, ny, nz, data, min, max, max_density }
+    }
+
+    // Load a grid from a raw volume file: an ASCII header line "nx ny nz", followed by
+    // nx * ny * nz little endian f32 densities, x varying fastest.
+    pub fn load(path: &str, min: Point3, max: Point3) -> io::Result<VoxelGrid> {
+        let file = File::open(path)?;
+        let file_length = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+
+        let mut header = String::new();
+        let header_length = reader.read_line(&mut header)? as u64;
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, message));
+        let dims: Vec<usize> = header
+            .split_whitespace()
+            .map(|x| x.parse::<usize>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid("header must be \"nx ny nz\""))?;
+        if dims.len() != 3 || dims.iter().any(|&d| d == 0) {
+            return Err(invalid("header must be \"nx ny nz\""));
+        }
+
+        // Checked against the file before allocating, as the header may be anything.
+        let length = dims[0]
+            .checked_mul(dims[1])
+            .and_then(|n| n.checked_mul(dims[2]))
+            .and_then(|n| n.checked_mul(4))
+            .filter(|&n| n as u64 <= file_length.saturating_sub(header_length))
+            .ok_or_else(|| invalid("the file is shorter than its header says"))?;
+        let mut bytes = vec![0u8; length];
+        reader.read_exact(&mut bytes)?;
+        let data: Vec<f64> = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64)
+            .collect();
+        // An infinite density would be the majorant, with which free flights never get anywhere.
+        if data.iter().any(|d| !d.is_finite()) {
+            return Err(invalid("densities must be finite"));
+        }
+        let data = data.into_iter().map(|d| d.max(0.0)).collect();
+
+        Ok(VoxelGrid::from(dims[0], dims[1], dims[2], data, min, max))
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize) -> f64 {
+        self.data[(z * self.ny + y) * self.nx + x]
+    }
+}
+
+impl DensityField for VoxelGrid {
+    fn density(&self, point: &Point3) -> f64 {
+        // Continuous grid coordinates, with voxel centers at integers.
+        let grid = |value: f64, min: f64, max: f64, n: usize| (value - min) / (max - min) * n as f64 - 0.5;
+        let gx = grid(point.x(), self.min.x(), self.max.x(), self.nx);
+        let gy = grid(point.y(), self.min.y(), self.max.y(), self.ny);
+        let gz = grid(point.z(), self.min.z(), self.max.z(), self.nz);
+        if gx < -0.5 || gy < -0.5 || gz < -0.5
+            || gx > self.nx as f64 - 0.5 || gy > self.ny as f64 - 0.5 || gz > self.nz as f64 - 0.5 {
+            return 0.0;
+        }
+
+        let clamp = |g: f64, n: usize| -> (usize, usize, f64) {
+            let g = g.clamp(0.0, (n - 1) as f64);
+            let i = (g.floor() as usize).min(n - 1);
+            (i, (i + 1).min(n - 1), g - i as f64)
+        };
+        let (x0, x1, fx) = clamp(gx, self.nx);
+        let (y0, y1, fy) = clamp(gy, self.ny);
+        let (z0, z1, fz) = clamp(gz, self.nz);
+
+        let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+        let c00 = lerp(self.voxel(x0, y0, z0), self.voxel(x1, y0, z0), fx);
+        let c10 = lerp(self.voxel(x0, y1, z0), self.voxel(x1, y1, z0), fx);
+        let c01 = lerp(self.voxel(x0, y0, z1), self.voxel(x1, y0, z1), fx);
+        let c11 = lerp(self.voxel(x0, y1, z1), self.voxel(x1, y1, z1), fx);
+        lerp(lerp(c00, c10, fy), lerp(c01, c11, fy), fz)
+    }
+
+    fn max_density(&self) -> f64 {
+        self.max_density
+    }
+}
@@ -0,0 +1,55 @@
+use std::sync::Mutex;
+
+use crate::common::*;
+
+// Accumulation buffer shared by render threads.
+// 'pixels' holds per-pixel estimates, 'splats' holds contributions that land on
+// arbitrary pixels (e.g. light subpaths connected to the camera).
+pub struct Film {
+    width: u32,
+    height: u32,
+    pixels: Vec<Mutex<Color>>,
+    splats: Vec<Mutex<Color>>,
+}
+
+#[allow(dead_code)]
+impl Film {
+    pub fn new(width: u32, height: u32) -> Film {
+        let size = (width * height) as usize;
+        Film {
+            width,
+            height,
+            pixels: (0..size).map(|_| Mutex::new(Color::new())).collect(),
+            splats: (0..size).map(|_| Mutex::new(Color::new())).collect(),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn add_sample(&self, i: u32, j: u32, color: Color) {
+        *self.pixels[self.index(i, j)].lock().unwrap() += color;
+    }
+
+    pub fn add_splat(&self, i: u32, j: u32, color: Color) {
+        *self.splats[self.index(i, j)].lock().unwrap() += color;
+    }
+
+    // Linear pixel colours, row by row. Pixel samples and splats are both averaged over
+    // 'samples_per_pixel'.
+    pub fn colors(&self, samples_per_pixel: u32) -> Vec<Color> {
+        self.pixels
+            .iter()
+            .zip(self.splats.iter())
+            .map(|(pixel, splat)| (*pixel.lock().unwrap() + *splat.lock().unwrap()) / samples_per_pixel as f64)
+            .collect()
+    }
+
+    fn index(&self, i: u32, j: u32) -> usize {
+        (j * self.width + i) as usize
+    }
+}
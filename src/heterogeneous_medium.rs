@@ -0,0 +1,190 @@
+use std::sync::Arc;
+
+use crate::common::*;
+use crate::constant_medium::boundary_segment;
+use crate::density_field::DensityField;
+use crate::hittable::{ Hittable, HitRecord };
+use crate::material::{ Material, Isotropic };
+use crate::aabb::Aabb;
+use crate::spectrum;
+
+// A volume whose density varies in space (clouds, explosions), bounded by a closed hittable.
+// Free flights are sampled with delta (Woodcock) tracking against the majorant
+// density_scale * max_density: tentative collisions are accepted with probability
+// density / majorant, the rejected ones are null collisions that leave the ray unchanged.
+pub struct HeterogeneousMedium {
+    boundary: Arc<dyn Hittable + Send + Sync>,
+    density: Arc<dyn DensityField + Send + Sync>,
+    density_scale: f64,
+    material: Arc<dyn Material + Send + Sync>,
+}
+
+#[allow(dead_code)]
+impl HeterogeneousMedium {
+    pub fn from(
+        boundary: Arc<dyn Hittable + Send + Sync>,
+        density: Arc<dyn DensityField + Send + Sync>,
+        density_scale: f64,
+        albedo: &Color,
+    ) -> HeterogeneousMedium {
+        HeterogeneousMedium::with_phase_function(boundary, density, density_scale, Arc::new(Isotropic::from(albedo)))
+    }
+
+    pub fn with_phase_function(
+        boundary: Arc<dyn Hittable + Send + Sync>,
+        density: Arc<dyn DensityField + Send + Sync>,
+        density_scale: f64,
+        phase_function: Arc<dyn Material + Send + Sync>,
+    ) -> HeterogeneousMedium {
+        HeterogeneousMedium {
+            boundary,
+            density,
+            density_scale,
+            material: phase_function,
+        }
+    }
+
+    // A glowing medium (fire): every collision at x emits 'emission' * temperature(x),
+    // so light is given off where the medium is both dense and hot.
+    pub fn emissive(
+        boundary: Arc<dyn Hittable + Send + Sync>,
+        density: Arc<dyn DensityField + Send + Sync>,
+        density_scale: f64,
+        phase_function: Arc<dyn Material + Send + Sync>,
+        emission: &Color,
+        temperature: Arc<dyn DensityField + Send + Sync>,
+    ) -> HeterogeneousMedium {
+        HeterogeneousMedium {
+            boundary,
+            density,
+            density_scale,
+            material: Arc::new(EmissivePhaseFunction {
+                phase_function,
+                emission: *emission,
+                temperature,
+            }),
+        }
+    }
+
+    fn majorant(&self) -> f64 {
+        self.density_scale * self.density.max_density()
+    }
+
+    // Distance along the ray, in units of t, to the next tentative collision.
+    fn free_flight(&self, ray_length: f64) -> f64 {
+        -(1.0 - random_double()).ln() / (self.majorant() * ray_length)
+    }
+}
+
+impl Hittable for HeterogeneousMedium {
+    fn hit(&self, ray: &Ray, ray_t: &Interval) -> Option<HitRecord> {
+        if self.majorant() <= 0.0 {
+            return None;
+        }
+        let (t_enter, t_exit) = boundary_segment(self.boundary.as_ref(), ray, ray_t)?;
+        let ray_length = ray.direction().abs();
+
+        let mut t = t_enter;
+        loop {
+            t += self.free_flight(ray_length);
+            if t >= t_exit {
+                return None;
+            }
+
+            let point = ray.at(t);
+            if random_double() * self.majorant() < self.density_scale * self.density.density(&point) {
+                return Some(HitRecord {
+                    point,
+                    normal: Vec3::from(1.0, 0.0, 0.0), // Arbitrary, see 'Material::is_volumetric'
+                    front_face: true,
+                    t,
+                    u: 0.0,
+                    v: 0.0,
+                    material: Arc::clone(&self.material),
+                });
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+
+    // Ratio tracking: instead of stopping at the first real collision, every tentative
+    // collision multiplies the estimate by the probability that it was a null collision.
+    fn transmittance(&self, ray: &Ray, ray_t: &Interval) -> Color {
+        let white = Color::from(1.0, 1.0, 1.0);
+        if self.majorant() <= 0.0 {
+            return white;
+        }
+        let Some((t_enter, t_exit)) = boundary_segment(self.boundary.as_ref(), ray, ray_t) else {
+            return white;
+        };
+        let ray_length = ray.direction().abs();
+
+        let mut transmittance = 1.0;
+        let mut t = t_enter;
+        loop {
+            t += self.free_flight(ray_length);
+            if t >= t_exit || transmittance <= 0.0 {
+                return transmittance * white;
+            }
+
+            let density = self.density_scale * self.density.density(&ray.at(t));
+            transmittance *= 1.0 - density / self.majorant();
+        }
+    }
+}
+
+// === Emission ===
+
+// Phase function of an emissive medium, scattering like the wrapped one.
+struct EmissivePhaseFunction {
+    phase_function: Arc<dyn Material + Send + Sync>,
+    emission: Color,
+    temperature: Arc<dyn DensityField + Send + Sync>,
+}
+
+impl Material for EmissivePhaseFunction {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+    ) -> Option<(Color, Ray)> {
+        self.phase_function.scatter(r_in, hit_record)
+    }
+
+    fn emitted(
+        &self,
+        _r_in: &Ray,
+        hit_record: &HitRecord,
+    ) -> Color {
+        spectrum::upsample(&self.emission) * self.temperature.density(&hit_record.point)
+    }
+
+    fn eval(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> Color {
+        self.phase_function.eval(r_in, hit_record, scattered)
+    }
+
+    fn pdf(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> f64 {
+        self.phase_function.pdf(r_in, hit_record, scattered)
+    }
+
+    fn is_volumetric(&self) -> bool {
+        true
+    }
+
+    fn is_specular(&self, hit_record: &HitRecord) -> bool {
+        self.phase_function.is_specular(hit_record)
+    }
+}
@@ -4,12 +4,16 @@ use crate::common::*;
 use crate::ray::Ray;
 use crate::vec3::{ Vec3, Point3 };
 use crate::material::Material;
+use crate::aabb::Aabb;
 
+#[derive(Clone)]
 pub struct HitRecord {
     pub point: Point3,
     pub normal: Vec3,
     pub front_face: bool,
     pub t: f64,
+    pub u: f64, // Surface coordinates in [0, 1] for textures
+    pub v: f64,
     pub material: Arc<dyn Material + Send + Sync>,
 }
 
@@ -19,10 +23,11 @@ impl HitRecord {
         ray: &Ray, 
         outward_normal: &Vec3,
         t: f64,
+        (u, v): (f64, f64),
         material: Arc<dyn Material + Send + Sync>,
     ) -> HitRecord {
 
-        let front_face = ray.direction().dot(&outward_normal) < 0.0;
+        let front_face = ray.direction().dot(outward_normal) < 0.0;
         let normal = if front_face {
             *outward_normal
         } else {
@@ -34,6 +39,8 @@ impl HitRecord {
             normal,
             front_face,
             t,
+            u,
+            v,
             material,
         }
     }
@@ -41,4 +48,25 @@ impl HitRecord {
 
 pub trait Hittable {
     fn hit(&self, ray: &Ray, ray_t: &Interval) -> Option<HitRecord>;
+
+    // World space box enclosing everything 'hit' can return, at any time.
+    fn bounding_box(&self) -> Aabb;
+
+    // Sample a point on the surface as it is at 'time', used to start light subpaths.
+    // Returns the record at that point (facing outwards) and its density with respect to area.
+    fn sample_surface(&self, _time: f64) -> Option<(HitRecord, f64)> {
+        None
+    }
+
+    // Density with respect to area with which 'sample_surface' returns 'point',
+    // where the surface has the (either sided) unit 'normal'.
+    fn surface_pdf(&self, _point: &Point3, _normal: &Vec3, _time: f64) -> f64 {
+        0.0
+    }
+
+    // Fraction of light carried along 'ray' within 'ray_t' that gets through this object, per
+    // channel. Surfaces block it completely; media return an unbiased estimate instead.
+    fn transmittance(&self, ray: &Ray, ray_t: &Interval) -> Color {
+        if self.hit(ray, ray_t).is_some() { Color::new() } else { Color::from(1.0, 1.0, 1.0) }
+    }
 }
@@ -1,24 +1,29 @@
 use crate::hittable::{ Hittable, HitRecord };
 use crate::ray::Ray;
 use crate::common::*;
+use crate::aabb::Aabb;
 
 pub struct HittableList {
-    objects: Vec<Box<dyn Hittable + Send + Sync>>
+    objects: Vec<Box<dyn Hittable + Send + Sync>>,
+    bbox: Aabb,
 }
 
 #[allow(dead_code)]
 impl HittableList {
     pub fn new() -> HittableList {
         HittableList {
-            objects: Vec::new()
+            objects: Vec::new(),
+            bbox: Aabb::new(),
         }
     }
 
     pub fn clear(&mut self) {
         self.objects.clear();
+        self.bbox = Aabb::new();
     }
     
     pub fn add(&mut self, hittable: Box<dyn Hittable + Send + Sync>) {
+        self.bbox = self.bbox.union(&hittable.bounding_box());
         self.objects.push(hittable);
     }
 
@@ -39,4 +44,65 @@ impl HittableList {
             None
         }
     }
+
+    // Pick one object uniformly, then sample a point on it.
+    pub fn sample_surface(&self, time: f64) -> Option<(HitRecord, f64)> {
+        if self.objects.is_empty() {
+            return None;
+        }
+        let index = ((random_double() * self.objects.len() as f64) as usize).min(self.objects.len() - 1);
+        let (hit_record, pdf) = self.objects[index].sample_surface(time)?;
+        Some((hit_record, pdf / self.objects.len() as f64))
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    pub fn surface_pdf(&self, point: &Point3, normal: &Vec3, time: f64) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+        let sum: f64 = self.objects.iter().map(|object| object.surface_pdf(point, normal, time)).sum();
+        sum / self.objects.len() as f64
+    }
+
+    // Product of the transmittances of all objects, unlike 'hit' which stops at the nearest.
+    pub fn transmittance(&self, ray: &Ray, ray_t: &Interval) -> Color {
+        let mut transmittance = Color::from(1.0, 1.0, 1.0);
+        for object in self.objects.iter() {
+            transmittance = transmittance * object.transmittance(ray, ray_t);
+            if transmittance.is_zero() {
+                break;
+            }
+        }
+        transmittance
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+}
+
+// Lets a whole list be shared by several 'Transform' instances.
+impl Hittable for HittableList {
+    fn hit(&self, ray: &Ray, ray_t: &Interval) -> Option<HitRecord> {
+        HittableList::hit(self, ray, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn sample_surface(&self, time: f64) -> Option<(HitRecord, f64)> {
+        HittableList::sample_surface(self, time)
+    }
+
+    fn surface_pdf(&self, point: &Point3, normal: &Vec3, time: f64) -> f64 {
+        HittableList::surface_pdf(self, point, normal, time)
+    }
+
+    fn transmittance(&self, ray: &Ray, ray_t: &Interval) -> Color {
+        HittableList::transmittance(self, ray, ray_t)
+    }
 }
@@ -1,3 +1,4 @@
+#[derive(Clone, Copy)]
 pub struct Interval {
     pub min: f64,
     pub max: f64
@@ -18,6 +19,15 @@ impl Interval {
         Interval { min, max }
     }
 
+    pub fn size(&self) -> f64 {
+        self.max - self.min
+    }
+
+    // The smallest interval enclosing both.
+    pub fn union(&self, other: &Interval) -> Interval {
+        Interval { min: self.min.min(other.min), max: self.max.max(other.max) }
+    }
+
     pub fn contains(&self, x: f64) -> bool {
         self.min <= x && x <= self.max
     }
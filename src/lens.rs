@@ -0,0 +1,432 @@
+use std::fs;
+use std::io;
+use std::sync::Arc;
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::common::*;
+
+// A camera that traces rays through a real multi-element lens (Kolb et al., as the realistic
+// camera of pbrt). Vignetting, distortion and focus breathing come from the lens itself.
+//
+// Lens space: the film lies in the plane z = 0 and the lens in front of it along +z, with the
+// optical axis on z. Rays leave the film towards the rear element through the exit pupil,
+// which is bounded ahead of time for rings of the film so few samples are wasted.
+
+// One surface of a lens, listed from the front (scene side) to the back (film side).
+// Lengths are in scene units: prescriptions in millimetres are scaled to metres.
+#[derive(Clone, Copy)]
+pub struct LensElement {
+    pub curvature_radius: f64, // Positive with the centre of curvature towards the film, 0 for the aperture stop
+    pub thickness: f64,        // Distance along the axis to the next surface, or to the film
+    pub eta: f64,              // Index of refraction behind the surface, 1 (or 0) for air
+    pub aperture_radius: f64,
+}
+
+// A lens design, as a table of surfaces.
+#[derive(Clone)]
+pub struct LensPrescription {
+    pub elements: Vec<LensElement>,
+}
+
+#[allow(dead_code)]
+impl LensPrescription {
+    pub fn from(elements: Vec<LensElement>) -> LensPrescription {
+        LensPrescription { elements }
+    }
+
+    // Rows of "radius thickness ior aperture_diameter" in millimetres, front to back, as in the
+    // lens files of pbrt. The thickness of the last row is replaced when focusing.
+    pub fn from_table(rows: &[[f64; 4]]) -> LensPrescription {
+        LensPrescription {
+            elements: rows
+                .iter()
+                .map(|row| LensElement {
+                    curvature_radius: row[0] * 0.001,
+                    thickness: row[1] * 0.001,
+                    eta: row[2],
+                    aperture_radius: row[3] * 0.001 / 2.0,
+                })
+                .collect(),
+        }
+    }
+
+    // A table as in 'from_table', one row per line. '#' starts a comment.
+    pub fn load(path: &str) -> io::Result<LensPrescription> {
+        let text = fs::read_to_string(path).map_err(|error| io::Error::new(error.kind(), format!("{}: {}", path, error)))?;
+        let invalid = |line: usize, message: &str| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}:{}: {}", path, line, message))
+        };
+
+        let mut rows = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let values: Vec<f64> = line
+                .split_whitespace()
+                .map(|x| x.parse::<f64>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| invalid(index + 1, "expected numbers"))?;
+            if values.len() != 4 {
+                return Err(invalid(index + 1, "rows must be \"radius thickness ior aperture\""));
+            }
+            rows.push([values[0], values[1], values[2], values[3]]);
+        }
+        if rows.is_empty() {
+            return Err(invalid(0, "no lens elements"));
+        }
+        Ok(LensPrescription::from_table(&rows))
+    }
+}
+
+// How a prescription is used by a camera. See 'CameraCreateInfo::lens'.
+#[derive(Clone)]
+pub struct LensInfo {
+    pub prescription: Arc<LensPrescription>,
+    pub film_diagonal: f64,             // Millimetres
+    pub aperture_diameter: Option<f64>, // Millimetres, to stop the aperture down
+}
+
+impl LensInfo {
+    pub fn from(prescription: Arc<LensPrescription>) -> LensInfo {
+        LensInfo {
+            prescription,
+            film_diagonal: 35.0,
+            aperture_diameter: None,
+        }
+    }
+}
+
+// Axis-aligned rectangle on a plane of constant z.
+#[derive(Clone, Copy)]
+struct Bounds2 {
+    min: (f64, f64),
+    max: (f64, f64),
+}
+
+impl Bounds2 {
+    fn new() -> Bounds2 {
+        Bounds2 { min: (INFINITY, INFINITY), max: (-INFINITY, -INFINITY) }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.min.0 >= self.max.0 || self.min.1 >= self.max.1
+    }
+
+    fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.min.0 && x <= self.max.0 && y >= self.min.1 && y <= self.max.1
+    }
+
+    fn add(&mut self, x: f64, y: f64) {
+        self.min = (self.min.0.min(x), self.min.1.min(y));
+        self.max = (self.max.0.max(x), self.max.1.max(y));
+    }
+
+    fn area(&self) -> f64 {
+        if self.is_empty() { 0.0 } else { (self.max.0 - self.min.0) * (self.max.1 - self.min.1) }
+    }
+
+    fn lerp(&self, u: f64, v: f64) -> (f64, f64) {
+        (self.min.0 + u * (self.max.0 - self.min.0), self.min.1 + v * (self.max.1 - self.min.1))
+    }
+}
+
+// Rings of the film with their own exit pupil bounds, and samples to find each.
+const EXIT_PUPIL_RINGS: usize = 64;
+const EXIT_PUPIL_SAMPLES: u64 = 16384;
+
+// A prescription focused for a camera, with its exit pupils.
+pub struct LensSystem {
+    elements: Vec<LensElement>, // The last thickness is the distance from the rear element to the film
+    film_width: f64,
+    film_height: f64,
+    exit_pupils: Vec<Bounds2>,
+    max_pupil_area: f64,
+}
+
+#[allow(dead_code)]
+impl LensSystem {
+    // Fails if the lens cannot focus at 'focus_distance' or no light reaches the film.
+    pub fn new(info: &LensInfo, aspect_ratio: f64, focus_distance: f64) -> Result<LensSystem, String> {
+        let mut lens = LensSystem::unfocused(info, aspect_ratio)?;
+        let film_distance = lens.focus(focus_distance)?;
+        lens.elements.last_mut().unwrap().thickness = film_distance;
+
+        let diagonal = info.film_diagonal * 0.001;
+        let ring = diagonal / 2.0 / EXIT_PUPIL_RINGS as f64;
+        lens.exit_pupils = (0..EXIT_PUPIL_RINGS)
+            .into_par_iter()
+            .map(|i| lens.bound_exit_pupil(i as f64 * ring, (i + 1) as f64 * ring))
+            .collect();
+        lens.max_pupil_area = lens.exit_pupils.iter().map(|b| b.area()).fold(0.0, f64::max);
+        if lens.max_pupil_area == 0.0 {
+            return Err("No light passes through the lens".to_string());
+        }
+        Ok(lens)
+    }
+
+    // Vertical field of view in radians of 'info' focused at infinity, from its focal length.
+    // Focusing closer narrows it slightly. None if the lens does not form an image.
+    pub fn vertical_fov(info: &LensInfo, aspect_ratio: f64) -> Option<f64> {
+        let lens = LensSystem::unfocused(info, aspect_ratio).ok()?;
+        let (pz0, fz0, _) = lens.thick_lens().ok()?;
+        let f = fz0 - pz0;
+        if !f.is_finite() || f <= 0.0 {
+            return None;
+        }
+        Some(2.0 * (lens.film_height / 2.0 / f).atan())
+    }
+
+    // The elements of 'info' and the film, without exit pupils, with the film where the
+    // prescription puts it.
+    fn unfocused(info: &LensInfo, aspect_ratio: f64) -> Result<LensSystem, String> {
+        let mut elements = info.prescription.elements.clone();
+        if elements.is_empty() {
+            return Err("The lens has no elements".to_string());
+        }
+        if let Some(diameter) = info.aperture_diameter {
+            for stop in elements.iter_mut().filter(|e| e.curvature_radius == 0.0) {
+                stop.aperture_radius = stop.aperture_radius.min(diameter * 0.001 / 2.0);
+            }
+        }
+
+        let diagonal = info.film_diagonal * 0.001;
+        let film_width = (diagonal * diagonal / (1.0 + 1.0 / (aspect_ratio * aspect_ratio))).sqrt();
+        Ok(LensSystem {
+            elements,
+            film_width,
+            film_height: film_width / aspect_ratio,
+            exit_pupils: Vec::new(),
+            max_pupil_area: 0.0,
+        })
+    }
+
+    // A ray leaving the film at (s, t), from the top left (0, 0) to the bottom right (1, 1),
+    // through the lens into the scene, in lens space. Returns the ray and a weight in [0, 1]
+    // for the light it carries to the film, relative to a ray on the axis through the largest pupil.
+    // None if the lens blocks the ray.
+    pub fn generate_ray(&self, s: f64, t: f64) -> Option<(Point3, Vec3, f64)> {
+        // The image is upside down and mirrored on the film.
+        let film = Point3::from((s - 0.5) * self.film_width, (t - 0.5) * self.film_height, 0.0);
+        let film = Point3::from(-film.x(), film.y(), 0.0);
+
+        let (rear, area) = self.sample_exit_pupil(film.x(), film.y());
+        let direction = rear - film;
+        let (origin, direction_out) = self.trace_from_film(&film, &direction)?;
+
+        // Irradiance falls off with cos^4 of the angle to the film normal, and with the area
+        // of the exit pupil, which is sampled uniformly.
+        let cos_theta = direction.unit().z();
+        let weight = cos_theta.powi(4) * area / self.max_pupil_area;
+        Some((origin, direction_out.unit(), weight))
+    }
+
+    fn rear_z(&self) -> f64 {
+        self.elements.last().unwrap().thickness
+    }
+
+    fn front_z(&self) -> f64 {
+        self.elements.iter().map(|e| e.thickness).sum()
+    }
+
+    fn rear_radius(&self) -> f64 {
+        self.elements.last().unwrap().aperture_radius
+    }
+
+    // Follow a ray from the film side through every surface to the scene.
+    fn trace_from_film(&self, origin: &Point3, direction: &Vec3) -> Option<(Point3, Vec3)> {
+        // Surfaces are laid out along -z, so flip the ray while tracing.
+        let mut o = Point3::from(origin.x(), origin.y(), -origin.z());
+        let mut d = Vec3::from(direction.x(), direction.y(), -direction.z());
+
+        let mut element_z = 0.0;
+        for i in (0..self.elements.len()).rev() {
+            let element = &self.elements[i];
+            element_z -= element.thickness;
+            let eta_i = element.eta;
+            let eta_t = if i > 0 && self.elements[i - 1].eta != 0.0 { self.elements[i - 1].eta } else { 1.0 };
+            (o, d) = LensSystem::trace_surface(element, element_z, &o, &d, eta_i, eta_t)?;
+        }
+        Some((Point3::from(o.x(), o.y(), -o.z()), Vec3::from(d.x(), d.y(), -d.z())))
+    }
+
+    // Follow a ray from the scene side through every surface to the film.
+    fn trace_from_scene(&self, origin: &Point3, direction: &Vec3) -> Option<(Point3, Vec3)> {
+        let mut o = Point3::from(origin.x(), origin.y(), -origin.z());
+        let mut d = Vec3::from(direction.x(), direction.y(), -direction.z());
+
+        let mut element_z = -self.front_z();
+        for i in 0..self.elements.len() {
+            let element = &self.elements[i];
+            let eta_i = if i == 0 || self.elements[i - 1].eta == 0.0 { 1.0 } else { self.elements[i - 1].eta };
+            let eta_t = if element.eta != 0.0 { element.eta } else { 1.0 };
+            (o, d) = LensSystem::trace_surface(element, element_z, &o, &d, eta_i, eta_t)?;
+            element_z += element.thickness;
+        }
+        Some((Point3::from(o.x(), o.y(), -o.z()), Vec3::from(d.x(), d.y(), -d.z())))
+    }
+
+    // Cross the surface 'element' at 'element_z' on the axis, from a medium of index 'eta_i'
+    // to 'eta_t'. None if the ray misses the surface or its aperture, or reflects totally.
+    fn trace_surface(
+        element: &LensElement,
+        element_z: f64,
+        o: &Point3,
+        d: &Vec3,
+        eta_i: f64,
+        eta_t: f64,
+    ) -> Option<(Point3, Vec3)> {
+        if element.curvature_radius == 0.0 {
+            // The aperture stop is a flat opening.
+            let t = (element_z - o.z()) / d.z();
+            let p = *o + t * *d;
+            if t < 0.0 || p.x() * p.x() + p.y() * p.y() > element.aperture_radius * element.aperture_radius {
+                return None;
+            }
+            return Some((p, *d));
+        }
+
+        let radius = element.curvature_radius;
+        let (t, normal) = LensSystem::intersect_sphere(radius, element_z + radius, o, d)?;
+        let p = *o + t * *d;
+        if p.x() * p.x() + p.y() * p.y() > element.aperture_radius * element.aperture_radius {
+            return None;
+        }
+        let direction = LensSystem::refract(&-d.unit(), &normal, eta_i / eta_t)?;
+        Some((p, direction))
+    }
+
+    // Hit of a ray with the sphere of 'radius' centred at 'z_center' on the axis, on the side
+    // that belongs to the lens surface. The normal faces the ray.
+    fn intersect_sphere(radius: f64, z_center: f64, o: &Point3, d: &Vec3) -> Option<(f64, Vec3)> {
+        let oc = *o - Point3::from(0.0, 0.0, z_center);
+        let a = d.abs2();
+        let half_b = oc.dot(d);
+        let c = oc.abs2() - radius * radius;
+        let discriminant = half_b * half_b - a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let sqrtd = discriminant.sqrt();
+        let (t0, t1) = ((-half_b - sqrtd) / a, (-half_b + sqrtd) / a);
+
+        let use_closer = (d.z() > 0.0) ^ (radius < 0.0);
+        let t = if use_closer { t0 } else { t1 };
+        if t < 0.0 {
+            return None;
+        }
+        let mut normal = (oc + t * *d).unit();
+        if normal.dot(d) > 0.0 {
+            normal = -normal;
+        }
+        Some((t, normal))
+    }
+
+    // Refraction of the unit direction 'wi' (pointing away from the surface) about 'normal'
+    // (on the side of 'wi'). None on total internal reflection.
+    fn refract(wi: &Vec3, normal: &Vec3, eta: f64) -> Option<Vec3> {
+        let cos_theta_i = normal.dot(wi);
+        let sin2_theta_i = (1.0 - cos_theta_i * cos_theta_i).max(0.0);
+        let sin2_theta_t = eta * eta * sin2_theta_i;
+        if sin2_theta_t >= 1.0 {
+            return None;
+        }
+        let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+        Some(eta * -*wi + (eta * cos_theta_i - cos_theta_t) * *normal)
+    }
+
+    // Positions on the (flipped) axis of the principal plane and focal point, from a ray
+    // parallel to the axis at height 'x' and the ray it leaves the lens as.
+    fn cardinal_points(x: f64, output: (Point3, Vec3)) -> (f64, f64) {
+        let (o, d) = output;
+        let t_focus = -o.x() / d.x();
+        let t_principal = (x - o.x()) / d.x();
+        (-(o.z() + t_principal * d.z()), -(o.z() + t_focus * d.z()))
+    }
+
+    // Distance from the rear element to the film that brings 'focus_distance' into focus,
+    // using the thick lens approximation of the system.
+    fn focus(&self, focus_distance: f64) -> Result<f64, String> {
+        let (pz0, fz0, pz1) = self.thick_lens()?;
+        let f = fz0 - pz0;
+        let z = -focus_distance;
+        let c = (pz1 - z - pz0) * (pz1 - z - 4.0 * f - pz0);
+        if c <= 0.0 {
+            return Err(format!("The lens cannot focus at a distance of {}", focus_distance));
+        }
+        let delta = 0.5 * (pz1 - z + pz0 - c.sqrt());
+        Ok(self.rear_z() + delta)
+    }
+
+    // The thick lens approximation of the system: the principal plane and focal point on the
+    // film side, and the principal plane on the scene side.
+    fn thick_lens(&self) -> Result<(f64, f64, f64), String> {
+        let x = 0.001 * (self.film_width * self.film_width + self.film_height * self.film_height).sqrt();
+        let failed = || "The lens does not form an image".to_string();
+
+        let scene_ray = (Point3::from(x, 0.0, self.front_z() + 1.0), Vec3::from(0.0, 0.0, -1.0));
+        let film_ray = self.trace_from_scene(&scene_ray.0, &scene_ray.1).ok_or_else(failed)?;
+        let (pz0, fz0) = LensSystem::cardinal_points(x, film_ray);
+
+        let film_ray = (Point3::from(x, 0.0, self.rear_z() - 1.0), Vec3::from(0.0, 0.0, 1.0));
+        let scene_ray = self.trace_from_film(&film_ray.0, &film_ray.1).ok_or_else(failed)?;
+        let (pz1, _) = LensSystem::cardinal_points(x, scene_ray);
+        Ok((pz0, fz0, pz1))
+    }
+
+    // Bounds on the rear element plane of the rays from film points at distance r0 to r1 from
+    // the axis (along x) that make it through the lens.
+    fn bound_exit_pupil(&self, r0: f64, r1: f64) -> Bounds2 {
+        let rear = 1.5 * self.rear_radius();
+        let rear_bounds = Bounds2 { min: (-rear, -rear), max: (rear, rear) };
+
+        let mut pupil = Bounds2::new();
+        for i in 0..EXIT_PUPIL_SAMPLES {
+            let film = Point3::from(r0 + (i as f64 + 0.5) / EXIT_PUPIL_SAMPLES as f64 * (r1 - r0), 0.0, 0.0);
+            let (x, y) = rear_bounds.lerp(radical_inverse(2, i), radical_inverse(3, i));
+            if pupil.contains(x, y) || self.trace_from_film(&film, &(Point3::from(x, y, self.rear_z()) - film)).is_some() {
+                pupil.add(x, y);
+            }
+        }
+        if pupil.is_empty() {
+            return rear_bounds;
+        }
+
+        // Grow by the spacing of the samples, which may have just missed the edge.
+        let grow = 2.0 * (2.0 * rear * 2.0f64.sqrt()) / (EXIT_PUPIL_SAMPLES as f64).sqrt();
+        Bounds2 {
+            min: (pupil.min.0 - grow, pupil.min.1 - grow),
+            max: (pupil.max.0 + grow, pupil.max.1 + grow),
+        }
+    }
+
+    // A point on the rear element plane within the exit pupil of the film point (x, y),
+    // and the area it was drawn from.
+    fn sample_exit_pupil(&self, x: f64, y: f64) -> (Point3, f64) {
+        let r = (x * x + y * y).sqrt();
+        let diagonal = (self.film_width * self.film_width + self.film_height * self.film_height).sqrt();
+        let ring = ((r / (diagonal / 2.0) * EXIT_PUPIL_RINGS as f64) as usize).min(EXIT_PUPIL_RINGS - 1);
+        let pupil = &self.exit_pupils[ring];
+        let (px, py) = pupil.lerp(random_double(), random_double());
+
+        // The pupils were found along +x; rotate to the angle of the film point.
+        let (sin_theta, cos_theta) = if r != 0.0 { (y / r, x / r) } else { (0.0, 1.0) };
+        let point = Point3::from(cos_theta * px - sin_theta * py, sin_theta * px + cos_theta * py, self.rear_z());
+        (point, pupil.area())
+    }
+}
+
+// The digits of 'i' in 'base' mirrored about the decimal point, a low discrepancy sequence.
+fn radical_inverse(base: u64, mut i: u64) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut inv = inv_base;
+    let mut result = 0.0;
+    while i > 0 {
+        result += (i % base) as f64 * inv;
+        i /= base;
+        inv *= inv_base;
+    }
+    result
+}
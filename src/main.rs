@@ -8,9 +8,34 @@ mod common;
 mod interval;
 mod camera;
 mod material;
+mod film;
+mod bdpt;
+mod photon;
+mod mlt;
+mod sampler;
+mod constant_medium;
+mod perlin;
+mod density_field;
+mod heterogeneous_medium;
+mod aabb;
+mod mat3;
+mod mat4;
+mod quat;
+mod onb;
+mod transform;
+mod animation;
+mod lens;
+mod aperture;
+mod microfacet;
+mod spectrum;
+mod texture;
+mod principled;
+mod mix;
+mod subsurface;
 
 use std::env;
 use std::fs::File;
+use std::path::Path;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::time::Instant;
@@ -24,21 +49,75 @@ use common::*;
 use sphere::Sphere;
 use camera::Camera;
 use camera::CameraCreateInfo;
+use camera::{ Projection, StereoInfo, StereoLayout };
+use lens::{ LensInfo, LensPrescription };
+use aperture::{ Aperture, ApertureImage };
+use spectrum::Ior;
+use texture::{ CheckerTexture, NoiseTexture };
+use principled::{ Principled, PrincipledInfo };
+use mix::{ MixMaterial, Blend };
+use subsurface::{ Subsurface, SubsurfaceInfo };
 use hittable_list::HittableList;
-use material::{ Lambertian, Metal, Dielectric };
+use material::{ Lambertian, Metal, Dielectric, DiffuseLight, HenyeyGreenstein, Conductor, ConductorPreset, RoughDielectric, Coated, ThinFilm, Substrate, OrenNayar, Velvet };
+use constant_medium::ConstantMedium;
+use density_field::NoiseDensity;
+use heterogeneous_medium::HeterogeneousMedium;
+use transform::{ Transform, Keyframe };
+use animation::{ CameraAnimation, TransformAnimation, Track, Interpolation };
+
+const USAGE: &str = "Usage: raytracer [--frames N..M] [--fps F] [--skip-existing] [--projection P] [--stereo L] [--lens FILE] [--aperture A] [--autofocus] [--frame] [--spectral] [output path]
+  --frames N..M     Render frames N to M (inclusive) of the animation instead of a still
+  --fps F           Frames per second of the animation (default 24)
+  --skip-existing   Do not render frames whose image already exists
+  --projection P    perspective (default), orthographic, fisheye, equisolid, equirectangular or cylindrical
+  --stereo L        Render both eyes, side-by-side (sbs) or top-bottom (tb); ODS for the panoramic projections
+  --lens FILE       Trace through the lens prescription in FILE, e.g. lenses/dgauss.50mm.dat
+  --aperture A      Shape of the bokeh: a number of blades (e.g. 6), or a grayscale image file (not with --lens)
+  --autofocus       Focus on the first surface towards the point the camera looks at
+  --frame           Move the camera back until the whole scene is in view
+  --spectral        Trace wavelengths instead of RGB, for dispersion (not with photon mapping)
+  With --frames, the '#'s in the output path are replaced by the zero padded frame number.";
+
+// Fraction of a frame the shutter stays open, in degrees as on a film camera.
+const SHUTTER_ANGLE: f64 = 180.0;
+
+struct Options {
+    path: String,
+    frames: Option<(u32, u32)>,
+    fps: f64,
+    skip_existing: bool,
+    projection: String,
+    stereo: Option<StereoLayout>,
+    lens: Option<Arc<LensPrescription>>,
+    aperture: Aperture,
+    autofocus: bool,
+    frame: bool,
+    spectral: bool,
+}
+
+const PROJECTIONS: [&str; 6] = ["perspective", "orthographic", "fisheye", "equisolid", "equirectangular", "cylindrical"];
 
 fn main() {
-    let args: Vec<String> = env::args().collect(); 
-    let path = if args.len() == 1 {
-        "output/book1/tmp.jpg"
-    } else if args.len() == 2 {
-        &args[1]
-    } else {
-        panic!("Image output path error.")
-    };
-    let mut output_file = file_setup(path);
+    let args: Vec<String> = env::args().collect();
+    let options = parse_args(&args[1..]).unwrap_or_else(|message| {
+        println!("{}", style(message).red());
+        println!("{}", USAGE);
+        std::process::exit(1);
+    });
+
+    match options.frames {
+        None => render_still(&options),
+        Some((first, last)) => render_animation(&options, first, last),
+    }
 
-    let camera = Camera::new(CameraCreateInfo{
+    std::process::exit(0);
+}
+
+fn render_still(options: &Options) {
+    let world = get_world3();
+    let lights = HittableList::new();
+
+    let camera = create_camera(with_view(CameraCreateInfo{
         samples_per_pixel: 500,
         max_depth: 50,
         image_width: 1920,
@@ -52,32 +131,230 @@ fn main() {
         focus_dist: 10.0,
 
         ..Default::default()
-    });
+    }, options, &world));
+
+    render_image(&camera, world, lights, &options.path);
+}
+
+// Frame f shows the scene at time f / fps, with the shutter open for SHUTTER_ANGLE of the frame.
+fn render_animation(options: &Options, first: u32, last: u32) {
+    for frame in first..=last {
+        let path = frame_path(&options.path, frame);
+        if options.skip_existing && Path::new(&path).exists() {
+            println!("Frame {} exists as \"{}\", skipped", frame, style(&path).yellow());
+            continue;
+        }
+
+        let time = frame as f64 / options.fps;
+        let shutter_close = time + SHUTTER_ANGLE / 360.0 / options.fps;
+        let (animation, world, lights) = get_animation1(time, shutter_close);
+        let camera = create_camera(with_view(animation.create_info(&CameraCreateInfo{
+            samples_per_pixel: 100,
+            max_depth: 50,
+            image_width: 960,
+
+            camera_position: Vec3::from(13.0, 2.0, 3.0),
+            look_at: Vec3::from(0.0, 0.0, 0.0),
+            vfov: 20.0,
+
+            defocus_angle: 0.3,
+            focus_dist: 10.0,
+
+            shutter_open: time,
+            shutter_close,
+
+            ..Default::default()
+        }, time), options, &world));
+
+        println!("Frame {} ({}..={})", style(frame).cyan(), first, last);
+        render_image(&camera, world, lights, &path);
+    }
+}
+
+// The camera for 'info', or exit with the reason it cannot be built (e.g. a lens that cannot
+// focus that close).
+fn create_camera(info: CameraCreateInfo) -> Camera {
+    Camera::new(info).unwrap_or_else(|message| {
+        println!("{}", style(format!("Invalid camera: {}", message)).red());
+        std::process::exit(1);
+    })
+}
+
+fn render_image(camera: &Camera, world: HittableList, lights: HittableList, path: &str) {
     let progress = progress_bar_setup(camera.image_height() * camera.image_width());
 
     let mut img: RgbImage = ImageBuffer::new(camera.image_width(), camera.image_height());
 
-    let world = get_world3();
-
     let t = Instant::now();
-    camera.render(Arc::new(world), &mut img, Arc::new(Mutex::new(progress)));
+    camera.render(Arc::new(world), Arc::new(lights), &mut img, Arc::new(Mutex::new(progress)));
     println!("done! cost: {:?}", t.elapsed());
 
     println!(
         "Ouput image as \"{}\"",
         style(path).yellow()
     );
+    // Written under another name first, so an interrupted run never leaves a truncated
+    // image behind for '--skip-existing' to mistake for a finished frame.
+    let partial_path = format!("{}.part", path);
+    let mut output_file = file_setup(&partial_path);
     let output_image = image::DynamicImage::ImageRgb8(img);
     match output_image.write_to(&mut output_file, image::ImageFormat::Jpeg) {
-        Ok(_) => {}
+        Ok(_) => {
+            if std::fs::rename(&partial_path, path).is_err() {
+                println!("{}", style("Outputting image fails.").red());
+            }
+        }
         Err(_) => println!("{}", style("Outputting image fails.").red()),
     }
-
-    std::process::exit(0);
 }
 
 // === Setup ===
 
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut path = None;
+    let mut frames = None;
+    let mut fps: f64 = 24.0;
+    let mut skip_existing = false;
+    let mut projection = "perspective".to_string();
+    let mut stereo = None;
+    let mut lens = None;
+    let mut aperture = Aperture::Circle;
+    let mut autofocus = false;
+    let mut frame = false;
+    let mut spectral = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frames" => {
+                let range = args.next().ok_or("--frames needs a range N..M")?;
+                let (first, last) = range.split_once("..").ok_or(format!("Invalid frame range \"{}\"", range))?;
+                let first: u32 = first.parse().map_err(|_| format!("Invalid first frame \"{}\"", first))?;
+                let last: u32 = last.parse().map_err(|_| format!("Invalid last frame \"{}\"", last))?;
+                if last < first {
+                    return Err(format!("Empty frame range \"{}\"", range));
+                }
+                frames = Some((first, last));
+            }
+            "--fps" => {
+                let value = args.next().ok_or("--fps needs a value")?;
+                fps = value.parse().map_err(|_| format!("Invalid frame rate \"{}\"", value))?;
+                if !fps.is_finite() || fps <= 0.0 {
+                    return Err(format!("Invalid frame rate \"{}\"", value));
+                }
+            }
+            "--skip-existing" => skip_existing = true,
+            "--autofocus" => autofocus = true,
+            "--frame" => frame = true,
+            "--spectral" => spectral = true,
+            "--stereo" => {
+                let name = args.next().ok_or("--stereo needs a layout")?;
+                stereo = match name.as_str() {
+                    "sbs" => Some(StereoLayout::SideBySide),
+                    "tb" => Some(StereoLayout::TopBottom),
+                    _ => return Err(format!("Unknown stereo layout \"{}\"", name)),
+                };
+            }
+            "--lens" => {
+                let file = args.next().ok_or("--lens needs a file")?;
+                let prescription = LensPrescription::load(file).map_err(|error| format!("Invalid lens: {}", error))?;
+                lens = Some(Arc::new(prescription));
+            }
+            "--aperture" => {
+                let shape = args.next().ok_or("--aperture needs a number of blades or an image")?;
+                aperture = match shape.parse::<u32>() {
+                    Ok(blades) if blades >= 3 => Aperture::Polygon { blades, rotation: 0.0 },
+                    Ok(_) => return Err(format!("Invalid number of blades \"{}\"", shape)),
+                    Err(_) => {
+                        let image = ApertureImage::load(shape).map_err(|error| format!("Invalid aperture \"{}\": {}", shape, error))?;
+                        if image.is_empty() {
+                            return Err(format!("Aperture \"{}\" lets no light through", shape));
+                        }
+                        Aperture::Image(Arc::new(image))
+                    }
+                };
+            }
+            "--projection" => {
+                let name = args.next().ok_or("--projection needs a name")?;
+                if !PROJECTIONS.contains(&name.as_str()) {
+                    return Err(format!("Unknown projection \"{}\"", name));
+                }
+                name.clone_into(&mut projection);
+            }
+            _ if arg.starts_with("--") => return Err(format!("Unknown option \"{}\"", arg)),
+            _ if path.is_none() => path = Some(arg.clone()),
+            _ => return Err("Image output path error.".to_string()),
+        }
+    }
+
+    if lens.is_some() && !matches!(aperture, Aperture::Circle) {
+        return Err("--aperture shapes the thin lens; a lens system has its own stop".to_string());
+    }
+
+    let default_path = if frames.is_some() { "output/animation/frame_####.jpg" } else { "output/book1/tmp.jpg" };
+    Ok(Options {
+        path: path.unwrap_or(default_path.to_string()),
+        frames,
+        fps,
+        skip_existing,
+        projection,
+        stereo,
+        lens,
+        aperture,
+        autofocus,
+        frame,
+        spectral,
+    })
+}
+
+// 'info' with the projection and stereo layout of 'options'. The orthographic view keeps the
+// height of the perspective view at the focus distance, the fisheyes cover 180 degrees.
+// Stereo views converge on the focus distance, with eyes a thirtieth of it apart.
+// A lens replaces the projection. Framing and autofocus look at 'world'; framing fits the
+// view of the projection or lens, and autofocus leaves the orthographic height as it is.
+fn with_view(info: CameraCreateInfo, options: &Options, world: &HittableList) -> CameraCreateInfo {
+    let projection = match options.projection.as_str() {
+        "orthographic" => Projection::Orthographic { height: 2.0 * info.focus_dist * (info.vfov.to_radians() / 2.0).tan() },
+        "fisheye" => Projection::FisheyeEquidistant { fov: 180.0 },
+        "equisolid" => Projection::FisheyeEquisolid { fov: 180.0 },
+        "equirectangular" => Projection::Equirectangular,
+        "cylindrical" => Projection::Cylindrical,
+        _ => Projection::Perspective,
+    };
+    let lens = options.lens.clone().map(LensInfo::from);
+    let mut info = CameraCreateInfo { projection, lens, aperture: options.aperture.clone(), spectral: options.spectral, ..info };
+    if options.frame {
+        info = info.frame(world);
+    }
+    if options.autofocus {
+        info = info.focus_on_look_at(world);
+    }
+
+    // After framing and autofocus, which move the focus distance.
+    let stereo = options.stereo.map(|layout| StereoInfo {
+        layout,
+        interocular_distance: info.focus_dist / 30.0,
+        convergence_distance: info.focus_dist,
+    });
+    CameraCreateInfo { stereo, ..info }
+}
+
+// Replace the run of '#'s in 'pattern' by the zero padded frame number,
+// or add the number before the extension if there is none.
+fn frame_path(pattern: &str, frame: u32) -> String {
+    if let Some(start) = pattern.find('#') {
+        let width = pattern[start..].chars().take_while(|&c| c == '#').count();
+        return format!("{}{:0width$}{}", &pattern[..start], frame, &pattern[start + width..], width = width);
+    }
+    let path = Path::new(pattern);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("frame");
+    let name = match path.extension().and_then(|e| e.to_str()) {
+        Some(extension) => format!("{}_{:04}.{}", stem, frame, extension),
+        None => format!("{}_{:04}", stem, frame),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
 fn file_setup(path_str: &str) -> File {
     let path = std::path::Path::new(path_str);
     let prefix = path.parent().unwrap();
@@ -181,3 +458,542 @@ fn get_world3() -> HittableList {
 
     world
 }
+
+// A small light inside a glass fixture above a glass ball on a diffuse floor.
+// Render with 'Integrator::Bidirectional' or 'Integrator::PhotonMapping' and a black background.
+#[allow(dead_code)]
+fn get_world4() -> (HittableList, HittableList) {
+    let mut world = HittableList::new();
+    let mut lights = HittableList::new();
+
+    let ground = Arc::new(Lambertian::from(&Color::from(0.73, 0.73, 0.73)));
+    let red    = Arc::new(Lambertian::from(&Color::from(0.65, 0.05, 0.05)));
+    let glass  = Arc::new(Dielectric::from(1.5));
+    let light  = Arc::new(DiffuseLight::from(&Color::from(60.0, 60.0, 60.0)));
+
+    world.add(Box::new(Sphere::from(Point3::from(0.0, -1000.0, 0.0), 1000.0, ground)));
+    world.add(Box::new(Sphere::from(Point3::from(-2.5, 1.0, -1.5), 1.0, red)));
+    world.add(Box::new(Sphere::from(Point3::from(0.0, 1.0, 0.0), 1.0, glass.clone())));
+
+    // Fixture
+    world.add(Box::new(Sphere::from(Point3::from(1.5, 4.0, 1.0), 0.6, glass)));
+    world.add(Box::new(Sphere::from(Point3::from(1.5, 4.0, 1.0), 0.2, light.clone())));
+    lights.add(Box::new(Sphere::from(Point3::from(1.5, 4.0, 1.0), 0.2, light)));
+
+    (world, lights)
+}
+
+// Smoke, and jade: a glass shell filled with a forward scattering green medium.
+#[allow(dead_code)]
+fn get_world5() -> HittableList {
+    let mut world = HittableList::new();
+
+    let ground = Arc::new(Lambertian::from(&Color::from(0.48, 0.83, 0.53)));
+    world.add(Box::new(Sphere::from(Point3::from(0.0, -1000.0, 0.0), 1000.0, ground)));
+
+    let smoke_boundary = Arc::new(Sphere::from(Point3::from(0.0, 1.0, -2.2), 1.0, Arc::new(Dielectric::from(1.0))));
+    world.add(Box::new(ConstantMedium::from(smoke_boundary, 1.5, &Color::from(0.9, 0.9, 0.9))));
+
+    let glass = Arc::new(Dielectric::from(1.6));
+    let jade_center = Point3::from(0.0, 1.0, 0.0);
+    world.add(Box::new(Sphere::from(jade_center, 1.0, glass.clone())));
+    let jade_boundary = Arc::new(Sphere::from(jade_center, 1.0, glass));
+    let jade_phase = Arc::new(HenyeyGreenstein::from(&Color::from(0.3, 0.8, 0.45), 0.6));
+    world.add(Box::new(ConstantMedium::with_phase_function(jade_boundary, 8.0, jade_phase)));
+
+    let metal = Arc::new(Metal::from(&Color::from(0.8, 0.8, 0.9), 0.0));
+    world.add(Box::new(Sphere::from(Point3::from(0.0, 1.0, 2.2), 1.0, metal)));
+
+    world
+}
+
+#[allow(dead_code)]
+fn get_world6() -> HittableList {
+    let mut world = HittableList::new();
+
+    let ground = Arc::new(Lambertian::from(&Color::from(0.48, 0.83, 0.53)));
+    world.add(Box::new(Sphere::from(Point3::from(0.0, -1000.0, 0.0), 1000.0, ground)));
+
+    let boundary_material = Arc::new(Dielectric::from(1.0));
+
+    let cloud_boundary = Arc::new(Sphere::from(Point3::from(0.0, 2.5, -1.5), 1.5, boundary_material.clone()));
+    let cloud_density = Arc::new(NoiseDensity::from(1.5, 5));
+    let cloud_phase = Arc::new(HenyeyGreenstein::from(&Color::from(0.95, 0.95, 0.95), 0.5));
+    world.add(Box::new(HeterogeneousMedium::with_phase_function(cloud_boundary, cloud_density, 6.0, cloud_phase)));
+
+    // The hot core of the fireball glows where the smoke is densest.
+    let fire_boundary = Arc::new(Sphere::from(Point3::from(0.0, 1.0, 1.5), 1.0, boundary_material));
+    let fire_density = Arc::new(NoiseDensity::from(3.0, 4));
+    let fire_phase = Arc::new(HenyeyGreenstein::from(&Color::from(0.4, 0.35, 0.3), 0.3));
+    world.add(Box::new(HeterogeneousMedium::emissive(
+        fire_boundary, fire_density.clone(), 10.0, fire_phase,
+        &Color::from(4.0, 1.6, 0.4), fire_density,
+    )));
+
+    world
+}
+
+// Instancing: one cluster of spheres shared by a ring of transforms.
+// Render with 'Integrator::Bidirectional' and a black background.
+#[allow(dead_code)]
+fn get_world7() -> (HittableList, HittableList) {
+    let mut world = HittableList::new();
+    let mut lights = HittableList::new();
+
+    let ground = Arc::new(Lambertian::from(&Color::from(0.73, 0.73, 0.73)));
+    world.add(Box::new(Sphere::from(Point3::from(0.0, -1000.0, 0.0), 1000.0, ground)));
+
+    let mut cluster = HittableList::new();
+    let gold = Arc::new(Metal::from(&Color::from(0.8, 0.6, 0.2), 0.2));
+    let blue = Arc::new(Lambertian::from(&Color::from(0.1, 0.2, 0.7)));
+    cluster.add(Box::new(Sphere::from(Point3::from(0.0, 0.5, 0.0), 0.5, blue)));
+    cluster.add(Box::new(Sphere::from(Point3::from(0.0, 1.25, 0.0), 0.25, gold.clone())));
+    cluster.add(Box::new(Sphere::from(Point3::from(0.6, 0.25, 0.0), 0.25, gold)));
+    let cluster: Arc<dyn hittable::Hittable + Send + Sync> = Arc::new(cluster);
+
+    // Each instance tumbles a little further towards the last orientation.
+    let tumbled = Quat::from_euler(0.0, 90.0, 30.0);
+    for i in 0..8 {
+        let orientation = Quat::slerp(&Quat::identity(), &tumbled, i as f64 / 7.0);
+        let matrix = Mat4::rotation(&Vec3::from(0.0, 1.0, 0.0), 45.0 * i as f64)
+            * Mat4::translation(&Vec3::from(3.0, 0.0, 0.0))
+            * Mat4::from_quat(&orientation);
+        world.add(Box::new(Transform::from(cluster.clone(), matrix)));
+    }
+
+    // A glass ellipsoid in the middle, lit by a long, thin tube overhead.
+    let unit_sphere = Arc::new(Sphere::from(Point3::new(), 1.0, Arc::new(Dielectric::from(1.5))));
+    world.add(Box::new(Transform::from(unit_sphere, Mat4::translation(&Vec3::from(0.0, 1.0, 0.0)) * Mat4::scaling(&Vec3::from(1.0, 1.0, 0.5)))));
+
+    let light = Arc::new(DiffuseLight::from(&Color::from(20.0, 20.0, 20.0)));
+    let tube = Mat4::translation(&Vec3::from(0.0, 4.0, 0.0)) * Mat4::scaling(&Vec3::from(3.0, 0.1, 0.1));
+    let light_sphere: Arc<dyn hittable::Hittable + Send + Sync> = Arc::new(Sphere::from(Point3::new(), 1.0, light));
+    world.add(Box::new(Transform::from(light_sphere.clone(), tube)));
+    lights.add(Box::new(Transform::from(light_sphere, tube)));
+
+    (world, lights)
+}
+
+// Motion blur: a rolling wheel and moving balls, lit by a light that moves too.
+// Render with 'shutter_open: 0.0, shutter_close: 1.0', using any integrator.
+#[allow(dead_code)]
+fn get_world8() -> (HittableList, HittableList) {
+    let mut world = HittableList::new();
+    let mut lights = HittableList::new();
+
+    let ground = Arc::new(Lambertian::from(&Color::from(0.5, 0.5, 0.5)));
+    world.add(Box::new(Sphere::from(Point3::from(0.0, -1000.0, 0.0), 1000.0, ground)));
+
+    // A wheel of spheres about the z axis, rolling a quarter turn to the right.
+    let mut wheel = HittableList::new();
+    let tire = Arc::new(Lambertian::from(&Color::from(0.1, 0.1, 0.1)));
+    let hub = Arc::new(Metal::from(&Color::from(0.8, 0.8, 0.8), 0.1));
+    for k in 0..12 {
+        let angle = degrees_to_radians(30.0 * k as f64);
+        wheel.add(Box::new(Sphere::from(Point3::from(angle.cos(), angle.sin(), 0.0), 0.2, tire.clone())));
+    }
+    wheel.add(Box::new(Sphere::from(Point3::new(), 0.35, hub)));
+    let wheel: Arc<dyn hittable::Hittable + Send + Sync> = Arc::new(wheel);
+
+    let quarter_turn = 90.0;
+    let roll = 2.0 * PI * 1.2 * quarter_turn / 360.0; // Distance covered without slipping
+    let keyframes = (0..=3).map(|k| {
+        let t = k as f64 / 3.0;
+        Keyframe::from(
+            t,
+            Vec3::from(-2.0 + roll * t, 1.2, 0.0),
+            Quat::from_axis_angle(&Vec3::from(0.0, 0.0, 1.0), -quarter_turn * t),
+            Vec3::from(1.0, 1.0, 1.0),
+        )
+    }).collect();
+    world.add(Box::new(Transform::animated(wheel, keyframes)));
+
+    let red = Arc::new(Lambertian::from(&Color::from(0.7, 0.1, 0.1)));
+    let glass = Arc::new(Dielectric::from(1.5));
+    world.add(Box::new(Sphere::moving(Point3::from(1.5, 0.5, 1.0), Point3::from(1.5, 1.2, 1.0), 0.5, red)));
+    world.add(Box::new(Sphere::moving(Point3::from(2.5, 0.6, -1.0), Point3::from(3.5, 0.6, -1.0), 0.6, glass)));
+
+    let light = Arc::new(DiffuseLight::from(&Color::from(15.0, 15.0, 15.0)));
+    world.add(Box::new(Sphere::moving(Point3::from(-1.0, 4.0, 2.0), Point3::from(1.0, 4.0, 2.0), 0.5, light.clone())));
+    lights.add(Box::new(Sphere::moving(Point3::from(-1.0, 4.0, 2.0), Point3::from(1.0, 4.0, 2.0), 0.5, light)));
+
+    (world, lights)
+}
+
+// Rough metals: gold, copper, aluminium and silver from smooth to rough, and brushed steel.
+// Render with 'Integrator::Bidirectional' and a black background.
+#[allow(dead_code)]
+fn get_world9() -> (HittableList, HittableList) {
+    let mut world = HittableList::new();
+    let mut lights = HittableList::new();
+
+    let ground = Arc::new(Lambertian::from(&Color::from(0.5, 0.5, 0.5)));
+    world.add(Box::new(Sphere::from(Point3::from(0.0, -1000.0, 0.0), 1000.0, ground)));
+
+    let presets = [ConductorPreset::Gold, ConductorPreset::Copper, ConductorPreset::Aluminium, ConductorPreset::Silver];
+    for (i, preset) in presets.iter().enumerate() {
+        let roughness = 0.15 + 0.25 * i as f64;
+        let x = -3.3 + 2.2 * i as f64;
+        world.add(Box::new(Sphere::from(Point3::from(x, 1.0, 0.0), 1.0, Arc::new(Conductor::preset(*preset, roughness)))));
+    }
+
+    // Brushed around the vertical axis.
+    let (eta, k) = (Color::from(2.9, 2.9, 2.9), Color::from(3.0, 3.0, 3.0));
+    let brushed = Arc::new(Conductor::anisotropic(&eta, &k, 0.1, 0.5, &Vec3::from(0.0, 1.0, 0.0)));
+    world.add(Box::new(Sphere::from(Point3::from(0.0, 0.7, 2.5), 0.7, brushed)));
+
+    let light = Arc::new(DiffuseLight::from(&Color::from(10.0, 10.0, 10.0)));
+    world.add(Box::new(Sphere::from(Point3::from(0.0, 7.0, 4.0), 1.5, light.clone())));
+    lights.add(Box::new(Sphere::from(Point3::from(0.0, 7.0, 4.0), 1.5, light)));
+
+    (world, lights)
+}
+
+// Frosted glass: spheres from smooth to rough in front of a row of small lights, and a rough pane.
+// Render with 'Integrator::Bidirectional' and a black background.
+#[allow(dead_code)]
+fn get_world10() -> (HittableList, HittableList) {
+    let mut world = HittableList::new();
+    let mut lights = HittableList::new();
+
+    let ground = Arc::new(Lambertian::from(&Color::from(0.5, 0.5, 0.5)));
+    world.add(Box::new(Sphere::from(Point3::from(0.0, -1000.0, 0.0), 1000.0, ground)));
+
+    for i in 0..4 {
+        let roughness = 0.1 + 0.2 * i as f64;
+        let x = -3.3 + 2.2 * i as f64;
+        world.add(Box::new(Sphere::from(Point3::from(x, 1.0, 0.0), 1.0, Arc::new(RoughDielectric::from(1.5, roughness)))));
+    }
+
+    // A thin pane of brushed acrylic in front.
+    let acrylic = Arc::new(RoughDielectric::from(1.49, 0.3));
+    world.add(Box::new(Transform::from(
+        Arc::new(Sphere::from(Point3::new(), 1.0, acrylic)),
+        Mat4::translation(&Vec3::from(0.0, 0.6, 2.5)) * Mat4::scaling(&Vec3::from(1.5, 0.6, 0.05)),
+    )));
+
+    // Small bright lights behind the spheres show how much each one blurs.
+    let light = Arc::new(DiffuseLight::from(&Color::from(15.0, 15.0, 15.0)));
+    for i in 0..5 {
+        let center = Point3::from(-4.0 + 2.0 * i as f64, 1.0, -3.0);
+        world.add(Box::new(Sphere::from(center, 0.3, light.clone())));
+        lights.add(Box::new(Sphere::from(center, 0.3, light.clone())));
+    }
+    let fill = Arc::new(DiffuseLight::from(&Color::from(4.0, 4.0, 4.0)));
+    world.add(Box::new(Sphere::from(Point3::from(0.0, 7.0, 4.0), 1.5, fill.clone())));
+    lights.add(Box::new(Sphere::from(Point3::from(0.0, 7.0, 4.0), 1.5, fill)));
+
+    (world, lights)
+}
+
+// Tinted glass: spheres of the same green glass from small to large grow darker and greener,
+// next to tinted frosted glass and a ball of glass filled with wine.
+// Render with 'Integrator::Bidirectional'.
+#[allow(dead_code)]
+fn get_world11() -> (HittableList, HittableList) {
+    let mut world = HittableList::new();
+    let mut lights = HittableList::new();
+
+    let ground = Arc::new(Lambertian::from(&Color::from(0.8, 0.8, 0.8)));
+    world.add(Box::new(Sphere::from(Point3::from(0.0, -1000.0, 0.0), 1000.0, ground)));
+
+    // Bottle green: 10% of red light is left after 1 unit.
+    let green = Arc::new(Dielectric::tinted(1.5, &Color::from(0.1, 0.6, 0.3), 1.0));
+    for (i, radius) in [0.25, 0.5, 0.75, 1.0].iter().enumerate() {
+        let x = -3.5 + 1.8 * i as f64;
+        world.add(Box::new(Sphere::from(Point3::from(x, *radius, 0.0), *radius, green.clone())));
+    }
+
+    let frosted = Arc::new(RoughDielectric::tinted(1.5, 0.3, &Color::from(0.3, 0.5, 0.9), 1.0));
+    world.add(Box::new(Sphere::from(Point3::from(3.6, 0.8, 0.0), 0.8, frosted)));
+
+    // The wine meets the glass, not air, so its surface refracts by the ratio of the indices.
+    let wine_center = Point3::from(0.0, 0.6, 2.5);
+    world.add(Box::new(Sphere::from(wine_center, 0.6, Arc::new(Dielectric::from(1.5)))));
+    let wine = Arc::new(Dielectric::tinted(1.34 / 1.5, &Color::from(0.5, 0.02, 0.1), 0.5));
+    world.add(Box::new(Sphere::from(wine_center, 0.54, wine)));
+
+    let light = Arc::new(DiffuseLight::from(&Color::from(10.0, 10.0, 10.0)));
+    world.add(Box::new(Sphere::from(Point3::from(0.0, 7.0, 4.0), 1.5, light.clone())));
+    lights.add(Box::new(Sphere::from(Point3::from(0.0, 7.0, 4.0), 1.5, light)));
+
+    (world, lights)
+}
+
+// Dispersion: a flint glass ball and a diamond throw rainbow edged caustics from a small light.
+// Render with '--spectral' and 'Integrator::Bidirectional' on a black background.
+#[allow(dead_code)]
+fn get_world12() -> (HittableList, HittableList) {
+    let mut world = HittableList::new();
+    let mut lights = HittableList::new();
+
+    let ground = Arc::new(Lambertian::from(&Color::from(0.8, 0.8, 0.8)));
+    world.add(Box::new(Sphere::from(Point3::from(0.0, -1000.0, 0.0), 1000.0, ground)));
+
+    let flint = Arc::new(Dielectric::dispersive(Ior::flint()));
+    world.add(Box::new(Sphere::from(Point3::from(-1.2, 1.0, 0.0), 1.0, flint)));
+    let diamond = Arc::new(Dielectric::dispersive(Ior::diamond()));
+    world.add(Box::new(Sphere::from(Point3::from(1.2, 0.7, 0.5), 0.7, diamond)));
+
+    // Low and to the side, so the caustics fall beside the balls.
+    let light = Arc::new(DiffuseLight::from(&Color::from(400.0, 400.0, 400.0)));
+    world.add(Box::new(Sphere::from(Point3::from(-6.0, 4.0, -2.0), 0.15, light.clone())));
+    lights.add(Box::new(Sphere::from(Point3::from(-6.0, 4.0, -2.0), 0.15, light)));
+
+    (world, lights)
+}
+
+// Principled materials: a marble, a gold ball, red car paint under a clearcoat, frosted
+// glass, brushed steel and velvet on a checkered floor. Render with 'Integrator::Bidirectional'.
+#[allow(dead_code)]
+fn get_world13() -> (HittableList, HittableList) {
+    let mut world = HittableList::new();
+    let mut lights = HittableList::new();
+
+    let checker = Arc::new(CheckerTexture::from(
+        1.0,
+        texture::solid(&Color::from(0.2, 0.3, 0.1)),
+        texture::solid(&Color::from(0.9, 0.9, 0.9)),
+    ));
+    let floor = Principled::from(PrincipledInfo { base_color: checker, roughness: texture::constant(0.3), ..Default::default() });
+    world.add(Box::new(Sphere::from(Point3::from(0.0, -1000.0, 0.0), 1000.0, Arc::new(floor))));
+
+    let materials = [
+        PrincipledInfo { base_color: Arc::new(NoiseTexture::from(4.0)), roughness: texture::constant(0.2), subsurface: texture::constant(0.5), ..Default::default() },
+        PrincipledInfo { base_color: texture::solid(&Color::from(1.0, 0.77, 0.34)), metallic: texture::constant(1.0), roughness: texture::constant(0.3), ..Default::default() },
+        PrincipledInfo { base_color: texture::solid(&Color::from(0.6, 0.02, 0.02)), roughness: texture::constant(0.6), clearcoat: texture::constant(1.0), ..Default::default() },
+        PrincipledInfo { base_color: texture::solid(&Color::from(0.9, 0.95, 1.0)), roughness: texture::constant(0.15), transmission: texture::constant(1.0), ..Default::default() },
+    ];
+    for (i, info) in materials.iter().enumerate() {
+        let x = -3.3 + 2.2 * i as f64;
+        world.add(Box::new(Sphere::from(Point3::from(x, 1.0, 0.0), 1.0, Arc::new(Principled::from(info.clone())))));
+    }
+
+    let brushed = PrincipledInfo {
+        base_color: texture::solid(&Color::from(0.6, 0.6, 0.62)),
+        metallic: texture::constant(1.0),
+        roughness: texture::constant(0.4),
+        anisotropic: texture::constant(0.9),
+        ..Default::default()
+    };
+    world.add(Box::new(Sphere::from(Point3::from(-1.2, 0.7, 2.5), 0.7, Arc::new(Principled::from(brushed)))));
+    let velvet = PrincipledInfo {
+        base_color: texture::solid(&Color::from(0.2, 0.02, 0.3)),
+        roughness: texture::constant(1.0),
+        specular: texture::constant(0.0),
+        sheen: texture::constant(1.0),
+        ..Default::default()
+    };
+    world.add(Box::new(Sphere::from(Point3::from(1.2, 0.7, 2.5), 0.7, Arc::new(Principled::from(velvet)))));
+
+    let light = Arc::new(DiffuseLight::from(&Color::from(10.0, 10.0, 10.0)));
+    world.add(Box::new(Sphere::from(Point3::from(0.0, 7.0, 4.0), 1.5, light.clone())));
+    lights.add(Box::new(Sphere::from(Point3::from(0.0, 7.0, 4.0), 1.5, light)));
+
+    (world, lights)
+}
+
+// Coated materials: red car paint over rough metal, amber lacquer over a pale diffuse base,
+// a rough coat over gold and a clear coat over blue plastic. Render with 'Integrator::Bidirectional'.
+#[allow(dead_code)]
+fn get_world14() -> (HittableList, HittableList) {
+    let mut world = HittableList::new();
+    let mut lights = HittableList::new();
+
+    let ground = Arc::new(Lambertian::from(&Color::from(0.5, 0.5, 0.5)));
+    world.add(Box::new(Sphere::from(Point3::from(0.0, -1000.0, 0.0), 1000.0, ground)));
+
+    let paint = Coated::tinted(Arc::new(Metal::from(&Color::from(0.9, 0.9, 0.9), 0.3)), 1.5, 0.0, &Color::from(0.8, 0.1, 0.1));
+    let lacquer = Coated::tinted(Arc::new(Lambertian::from(&Color::from(0.8, 0.7, 0.5))), 1.5, 0.0, &Color::from(0.9, 0.6, 0.3));
+    let gold = Coated::from(Arc::new(Conductor::preset(ConductorPreset::Gold, 0.2)), 1.5, 0.3);
+    let plastic = Coated::from(Arc::new(Lambertian::from(&Color::from(0.1, 0.2, 0.6))), 1.5, 0.0);
+    for (i, material) in vec![paint, lacquer, gold, plastic].into_iter().enumerate() {
+        let x = -3.3 + 2.2 * i as f64;
+        world.add(Box::new(Sphere::from(Point3::from(x, 1.0, 0.0), 1.0, Arc::new(material))));
+    }
+
+    let light = Arc::new(DiffuseLight::from(&Color::from(10.0, 10.0, 10.0)));
+    world.add(Box::new(Sphere::from(Point3::from(0.0, 7.0, 4.0), 1.5, light.clone())));
+    lights.add(Box::new(Sphere::from(Point3::from(0.0, 7.0, 4.0), 1.5, light)));
+
+    (world, lights)
+}
+
+// Mixed materials: rust eating into aluminium, worn red paint showing the copper beneath,
+// a checkered decal, and a blend of diffuse, mirror and gold. Render with 'Integrator::Bidirectional'.
+#[allow(dead_code)]
+fn get_world15() -> (HittableList, HittableList) {
+    let mut world = HittableList::new();
+    let mut lights = HittableList::new();
+
+    let ground = Arc::new(Lambertian::from(&Color::from(0.5, 0.5, 0.5)));
+    world.add(Box::new(Sphere::from(Point3::from(0.0, -1000.0, 0.0), 1000.0, ground)));
+
+    let aluminium = Arc::new(Conductor::preset(ConductorPreset::Aluminium, 0.2));
+    let rust = Arc::new(Lambertian::from(&Color::from(0.4, 0.15, 0.05)));
+    let rusty = MixMaterial::masked(aluminium, rust, Arc::new(NoiseTexture::from(3.0)));
+
+    let paint = Arc::new(Coated::from(Arc::new(Lambertian::from(&Color::from(0.6, 0.05, 0.05))), 1.5, 0.1));
+    let copper = Arc::new(Conductor::preset(ConductorPreset::Copper, 0.3));
+    let worn = MixMaterial::masked(paint, copper, Arc::new(NoiseTexture::from(8.0)));
+
+    let decal = Arc::new(CheckerTexture::from(0.4, texture::constant(0.0), texture::constant(1.0)));
+    let decaled = MixMaterial::masked(
+        Arc::new(Lambertian::from(&Color::from(0.8, 0.8, 0.8))),
+        Arc::new(Lambertian::from(&Color::from(0.1, 0.3, 0.7))),
+        decal,
+    );
+
+    let blend = Blend::constant(vec![
+        (Arc::new(Lambertian::from(&Color::from(0.2, 0.5, 0.2))), 0.5),
+        (Arc::new(Metal::from(&Color::from(0.9, 0.9, 0.9), 0.0)), 0.3),
+        (Arc::new(Conductor::preset(ConductorPreset::Gold, 0.3)), 0.2),
+    ]);
+
+    world.add(Box::new(Sphere::from(Point3::from(-3.3, 1.0, 0.0), 1.0, Arc::new(rusty))));
+    world.add(Box::new(Sphere::from(Point3::from(-1.1, 1.0, 0.0), 1.0, Arc::new(worn))));
+    world.add(Box::new(Sphere::from(Point3::from(1.1, 1.0, 0.0), 1.0, Arc::new(decaled))));
+    world.add(Box::new(Sphere::from(Point3::from(3.3, 1.0, 0.0), 1.0, Arc::new(blend))));
+
+    let light = Arc::new(DiffuseLight::from(&Color::from(10.0, 10.0, 10.0)));
+    world.add(Box::new(Sphere::from(Point3::from(0.0, 7.0, 4.0), 1.5, light.clone())));
+    lights.add(Box::new(Sphere::from(Point3::from(0.0, 7.0, 4.0), 1.5, light)));
+
+    (world, lights)
+}
+
+// Thin-film interference: a soap bubble of varying thickness, oil on a water puddle, a glass
+// marble with an anti-reflection coating and oxidised copper.
+#[allow(dead_code)]
+fn get_world16() -> (HittableList, HittableList) {
+    let mut world = HittableList::new();
+    let mut lights = HittableList::new();
+
+    let ground = Arc::new(Lambertian::from(&Color::from(0.5, 0.5, 0.5)));
+    world.add(Box::new(Sphere::from(Point3::from(0.0, -1000.0, 0.0), 1000.0, ground)));
+
+    let soap = ThinFilm::textured(1.33, Arc::new(NoiseTexture::from(2.0)), (200.0, 800.0), Substrate::Air);
+    let oil = ThinFilm::textured(1.5, Arc::new(NoiseTexture::from(1.0)), (300.0, 600.0), Substrate::Dielectric(1.33));
+    let coating = ThinFilm::from(1.38, 100.0, Substrate::Dielectric(1.5));
+    let oxide = ThinFilm::textured(2.4, Arc::new(NoiseTexture::from(0.5)), (50.0, 150.0), Substrate::metal(ConductorPreset::Copper));
+
+    world.add(Box::new(Sphere::from(Point3::from(-3.3, 1.0, 0.0), 1.0, Arc::new(soap))));
+    world.add(Box::new(Sphere::from(Point3::from(-1.1, -4.9, 1.0), 5.0, Arc::new(oil))));
+    world.add(Box::new(Sphere::from(Point3::from(1.1, 1.0, 0.0), 1.0, Arc::new(coating))));
+    world.add(Box::new(Sphere::from(Point3::from(3.3, 1.0, 0.0), 1.0, Arc::new(oxide))));
+
+    let light = Arc::new(DiffuseLight::from(&Color::from(10.0, 10.0, 10.0)));
+    world.add(Box::new(Sphere::from(Point3::from(0.0, 7.0, 4.0), 1.5, light.clone())));
+    lights.add(Box::new(Sphere::from(Point3::from(0.0, 7.0, 4.0), 1.5, light)));
+
+    (world, lights)
+}
+
+// Subsurface scattering: skin, wax, marble and milk, lit from behind so that light shows
+// through the thin edges. Red goes furthest in skin and wax, so their edges glow red.
+#[allow(dead_code)]
+fn get_world17() -> (HittableList, HittableList) {
+    let mut world = HittableList::new();
+    let mut lights = HittableList::new();
+
+    let ground = Arc::new(Lambertian::from(&Color::from(0.5, 0.5, 0.5)));
+    world.add(Box::new(Sphere::from(Point3::from(0.0, -1000.0, 0.0), 1000.0, ground)));
+
+    let materials = vec![
+        SubsurfaceInfo { color: Color::from(0.8, 0.5, 0.4), radius: Color::from(0.2, 0.08, 0.05), ..Default::default() },
+        SubsurfaceInfo { color: Color::from(0.9, 0.75, 0.5), radius: Color::from(0.3, 0.25, 0.15), roughness: 0.2, index_of_refraction: 1.45, ..Default::default() },
+        SubsurfaceInfo { color: Color::from(0.9, 0.9, 0.88), radius: Color::from(0.05, 0.05, 0.05), index_of_refraction: 1.5, ..Default::default() },
+        SubsurfaceInfo { color: Color::from(0.95, 0.93, 0.88), radius: Color::from(0.03, 0.02, 0.015), index_of_refraction: 1.35, anisotropy: 0.8, ..Default::default() },
+    ];
+    for (i, info) in materials.iter().enumerate() {
+        let boundary = Arc::new(Sphere::from(Point3::from(-3.3 + 2.2 * i as f64, 1.0, 0.0), 1.0, Arc::new(Lambertian::from(&Color::new()))));
+        world.add(Box::new(Subsurface::from(boundary, info)));
+    }
+
+    let light = Arc::new(DiffuseLight::from(&Color::from(10.0, 10.0, 10.0)));
+    world.add(Box::new(Sphere::from(Point3::from(0.0, 4.0, -4.0), 1.5, light.clone())));
+    lights.add(Box::new(Sphere::from(Point3::from(0.0, 4.0, -4.0), 1.5, light)));
+
+    (world, lights)
+}
+
+// Diffuse models side by side: Lambertian, Oren-Nayar clay and moon dust, and velvet, lit
+// from behind the camera where rough diffuse surfaces look flat.
+#[allow(dead_code)]
+fn get_world18() -> (HittableList, HittableList) {
+    let mut world = HittableList::new();
+    let mut lights = HittableList::new();
+
+    let ground = Arc::new(OrenNayar::from(&Color::from(0.45, 0.4, 0.35), 30.0));
+    world.add(Box::new(Sphere::from(Point3::from(0.0, -1000.0, 0.0), 1000.0, ground)));
+
+    let lambertian = Arc::new(Lambertian::from(&Color::from(0.7, 0.7, 0.7)));
+    let clay = Arc::new(OrenNayar::from(&Color::from(0.7, 0.4, 0.25), 20.0));
+    let moon = Arc::new(OrenNayar::from(&Color::from(0.7, 0.7, 0.7), 60.0));
+    let velvet = Arc::new(Velvet::from(&Color::from(0.3, 0.02, 0.05), &Color::from(0.9, 0.5, 0.55), 0.5));
+
+    world.add(Box::new(Sphere::from(Point3::from(-3.3, 1.0, 0.0), 1.0, lambertian)));
+    world.add(Box::new(Sphere::from(Point3::from(-1.1, 1.0, 0.0), 1.0, clay)));
+    world.add(Box::new(Sphere::from(Point3::from(1.1, 1.0, 0.0), 1.0, moon)));
+    world.add(Box::new(Sphere::from(Point3::from(3.3, 1.0, 0.0), 1.0, velvet)));
+
+    let light = Arc::new(DiffuseLight::from(&Color::from(10.0, 10.0, 10.0)));
+    world.add(Box::new(Sphere::from(Point3::from(0.0, 6.0, 12.0), 1.5, light.clone())));
+    lights.add(Box::new(Sphere::from(Point3::from(0.0, 6.0, 12.0), 1.5, light)));
+
+    (world, lights)
+}
+
+// A ball bouncing past a rolling wheel while the camera swings around, two seconds long.
+// Returns the camera animation and the scene as it moves between the shutter times.
+#[allow(dead_code)]
+fn get_animation1(shutter_open: f64, shutter_close: f64) -> (CameraAnimation, HittableList, HittableList) {
+    let mut world = HittableList::new();
+
+    let ground = Arc::new(Lambertian::from(&Color::from(0.5, 0.5, 0.5)));
+    world.add(Box::new(Sphere::from(Point3::from(0.0, -1000.0, 0.0), 1000.0, ground)));
+
+    // The ball eases over the top of each bounce and hits the ground at full speed.
+    let ball: Arc<dyn hittable::Hittable + Send + Sync> =
+        Arc::new(Sphere::from(Point3::new(), 0.5, Arc::new(Metal::from(&Color::from(0.8, 0.3, 0.3), 0.05))));
+    let mut bounce = TransformAnimation::default();
+    for (k, x) in [-2.0, -1.0, 0.0, 1.0, 2.0].iter().enumerate() {
+        let time = 0.5 * k as f64;
+        bounce.translation.add(time, Vec3::from(*x, 0.5, 1.5), Interpolation::Bezier);
+        if k < 4 {
+            bounce.translation.add(time + 0.25, Vec3::from(*x + 0.5, 2.0, 1.5), Interpolation::Bezier);
+        }
+    }
+    world.add(Box::new(bounce.transform(ball, shutter_open, shutter_close)));
+
+    // Two full turns while rolling across, at constant speed.
+    let mut wheel = HittableList::new();
+    let tire = Arc::new(Lambertian::from(&Color::from(0.1, 0.1, 0.1)));
+    let spoke = Arc::new(Lambertian::from(&Color::from(0.9, 0.8, 0.2)));
+    for k in 0..12 {
+        let angle = degrees_to_radians(30.0 * k as f64);
+        wheel.add(Box::new(Sphere::from(Point3::from(angle.cos(), angle.sin(), 0.0), 0.2, tire.clone())));
+        if k % 3 == 0 {
+            wheel.add(Box::new(Sphere::from(Point3::from(0.5 * angle.cos(), 0.5 * angle.sin(), 0.0), 0.15, spoke.clone())));
+        }
+    }
+    let wheel: Arc<dyn hittable::Hittable + Send + Sync> = Arc::new(wheel);
+    let radius = 1.2;
+    let mut roll = TransformAnimation::default();
+    roll.translation.add(0.0, Vec3::from(-4.0 * PI * radius / 2.0, radius, -1.0), Interpolation::Linear);
+    roll.translation.add(2.0, Vec3::from(4.0 * PI * radius / 2.0, radius, -1.0), Interpolation::Linear);
+    roll.rotation.add(0.0, Vec3::new(), Interpolation::Linear);
+    roll.rotation.add(2.0, Vec3::from(0.0, 0.0, -720.0), Interpolation::Linear);
+    world.add(Box::new(roll.transform(wheel, shutter_open, shutter_close)));
+
+    let mut camera = CameraAnimation::default();
+    camera.camera_position.add(0.0, Vec3::from(13.0, 2.0, 3.0), Interpolation::Bezier);
+    camera.camera_position.add(1.0, Vec3::from(9.0, 4.0, 9.0), Interpolation::Bezier);
+    camera.camera_position.add(2.0, Vec3::from(3.0, 2.0, 13.0), Interpolation::Bezier);
+    camera.look_at = Track::constant(Vec3::from(0.0, 0.8, 0.0));
+    camera.vfov.add(0.0, 25.0, Interpolation::Bezier);
+    camera.vfov.add(2.0, 35.0, Interpolation::Bezier);
+    camera.focus_dist = Track::constant(13.0);
+
+    (camera, world, HittableList::new())
+}
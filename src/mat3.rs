@@ -0,0 +1,122 @@
+use std::ops::Mul;
+
+use crate::vec3::Vec3;
+
+// Row major 3x3 matrix, the linear part of a transform, applied to column vectors.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Mat3 {
+    m: [[f64; 3]; 3],
+}
+
+impl Default for Mat3 {
+    fn default() -> Self {
+        Mat3::identity()
+    }
+}
+
+#[allow(dead_code)]
+impl Mat3 {
+    pub fn identity() -> Mat3 {
+        Mat3::scaling(&Vec3::from(1.0, 1.0, 1.0))
+    }
+
+    pub fn from(m: [[f64; 3]; 3]) -> Mat3 {
+        Mat3 { m }
+    }
+
+    // The matrix taking the x, y and z axes to 'a', 'b' and 'c'.
+    pub fn from_columns(a: &Vec3, b: &Vec3, c: &Vec3) -> Mat3 {
+        Mat3::from([
+            [a.x(), b.x(), c.x()],
+            [a.y(), b.y(), c.y()],
+            [a.z(), b.z(), c.z()],
+        ])
+    }
+
+    pub fn scaling(factors: &Vec3) -> Mat3 {
+        Mat3::from([
+            [factors.x(), 0.0, 0.0],
+            [0.0, factors.y(), 0.0],
+            [0.0, 0.0, factors.z()],
+        ])
+    }
+
+    // Counterclockwise rotation by 'degrees' about 'axis', looking down the axis (Rodrigues).
+    pub fn rotation(axis: &Vec3, degrees: f64) -> Mat3 {
+        let a = axis.unit();
+        let (x, y, z) = (a.x(), a.y(), a.z());
+        let (s, c) = degrees.to_radians().sin_cos();
+        let t = 1.0 - c;
+
+        Mat3::from([
+            [t * x * x + c,     t * x * y - s * z, t * x * z + s * y],
+            [t * x * y + s * z, t * y * y + c,     t * y * z - s * x],
+            [t * x * z - s * y, t * y * z + s * x, t * z * z + c    ],
+        ])
+    }
+
+    // Rotation about x, then y, then z, by the given angles in degrees.
+    pub fn from_euler(x: f64, y: f64, z: f64) -> Mat3 {
+        Mat3::rotation(&Vec3::from(0.0, 0.0, 1.0), z)
+            * Mat3::rotation(&Vec3::from(0.0, 1.0, 0.0), y)
+            * Mat3::rotation(&Vec3::from(1.0, 0.0, 0.0), x)
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> f64 {
+        self.m[row][column]
+    }
+
+    pub fn row(&self, row: usize) -> Vec3 {
+        Vec3::from(self.m[row][0], self.m[row][1], self.m[row][2])
+    }
+
+    pub fn column(&self, column: usize) -> Vec3 {
+        Vec3::from(self.m[0][column], self.m[1][column], self.m[2][column])
+    }
+
+    pub fn transpose(&self) -> Mat3 {
+        Mat3::from_columns(&self.row(0), &self.row(1), &self.row(2))
+    }
+
+    pub fn determinant(&self) -> f64 {
+        self.row(0).dot(&self.row(1).cross(&self.row(2)))
+    }
+
+    // Adjugate over determinant. None if the matrix is singular.
+    pub fn inverse(&self) -> Option<Mat3> {
+        let det = self.determinant();
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let (r0, r1, r2) = (self.row(0), self.row(1), self.row(2));
+        Some(Mat3::from_columns(&(r1.cross(&r2) / det), &(r2.cross(&r0) / det), &(r0.cross(&r1) / det)))
+    }
+
+    // The inverse transpose, which keeps normals perpendicular to transformed surfaces.
+    pub fn normal_matrix(&self) -> Option<Mat3> {
+        Some(self.inverse()?.transpose())
+    }
+}
+
+impl Mul for Mat3 {
+    type Output = Mat3;
+
+    // 'a * b' applies b first, then a.
+    fn mul(self, other: Mat3) -> Mat3 {
+        let mut m = [[0.0; 3]; 3];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.row(i).dot(&other.column(j));
+            }
+        }
+        Mat3 { m }
+    }
+}
+
+impl Mul<Vec3> for Mat3 {
+    type Output = Vec3;
+
+    fn mul(self, v: Vec3) -> Vec3 {
+        Vec3::from(self.row(0).dot(&v), self.row(1).dot(&v), self.row(2).dot(&v))
+    }
+}
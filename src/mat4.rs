@@ -0,0 +1,192 @@
+use std::ops::Mul;
+
+use crate::mat3::Mat3;
+use crate::onb::Onb;
+use crate::quat::Quat;
+use crate::vec3::{ Vec3, Point3 };
+
+// Row major 4x4 matrix of an affine transform, applied to column vectors.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Mat4 {
+    m: [[f64; 4]; 4],
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Mat4::identity()
+    }
+}
+
+#[allow(dead_code)]
+impl Mat4 {
+    pub fn identity() -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            row[i] = 1.0;
+        }
+        Mat4 { m }
+    }
+
+    pub fn from(m: [[f64; 4]; 4]) -> Mat4 {
+        Mat4 { m }
+    }
+
+    pub fn translation(offset: &Vec3) -> Mat4 {
+        let mut result = Mat4::identity();
+        for i in 0..3 {
+            result.m[i][3] = offset[i];
+        }
+        result
+    }
+
+    pub fn scaling(factors: &Vec3) -> Mat4 {
+        let mut result = Mat4::identity();
+        for i in 0..3 {
+            result.m[i][i] = factors[i];
+        }
+        result
+    }
+
+    // The transform applying 'linear', then moving by 'offset'.
+    pub fn from_linear(linear: &Mat3, offset: &Vec3) -> Mat4 {
+        let mut result = Mat4::translation(offset);
+        for i in 0..3 {
+            for j in 0..3 {
+                result.m[i][j] = linear.get(i, j);
+            }
+        }
+        result
+    }
+
+    // See 'Mat3::rotation'.
+    pub fn rotation(axis: &Vec3, degrees: f64) -> Mat4 {
+        Mat4::from_linear(&Mat3::rotation(axis, degrees), &Vec3::new())
+    }
+
+    // See 'Mat3::from_euler'.
+    pub fn from_euler(x: f64, y: f64, z: f64) -> Mat4 {
+        Mat4::from_linear(&Mat3::from_euler(x, y, z), &Vec3::new())
+    }
+
+    pub fn from_quat(rotation: &Quat) -> Mat4 {
+        rotation.to_mat4()
+    }
+
+    // Places an object at 'from' with its -z axis facing 'to' and its y axis towards 'up',
+    // like a camera. The inverse is the corresponding view matrix.
+    pub fn look_at(from: &Point3, to: &Point3, up: &Vec3) -> Mat4 {
+        Mat4::from_linear(&Onb::look_at(from, to, up).to_mat3(), from)
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> f64 {
+        self.m[row][column]
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut result = Mat4::identity();
+        for i in 0..4 {
+            for j in 0..4 {
+                result.m[i][j] = self.m[j][i];
+            }
+        }
+        result
+    }
+
+    // Gauss-Jordan elimination with partial pivoting. None if the matrix is singular.
+    pub fn inverse(&self) -> Option<Mat4> {
+        let mut a = self.m;
+        let mut inv = Mat4::identity().m;
+
+        for column in 0..4 {
+            let pivot = (column..4)
+                .max_by(|&i, &j| a[i][column].abs().total_cmp(&a[j][column].abs()))
+                .unwrap();
+            if a[pivot][column].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(column, pivot);
+            inv.swap(column, pivot);
+
+            let scale = 1.0 / a[column][column];
+            for k in 0..4 {
+                a[column][k] *= scale;
+                inv[column][k] *= scale;
+            }
+
+            for row in 0..4 {
+                if row == column {
+                    continue;
+                }
+                let factor = a[row][column];
+                for k in 0..4 {
+                    a[row][k] -= factor * a[column][k];
+                    inv[row][k] -= factor * inv[column][k];
+                }
+            }
+        }
+
+        Some(Mat4 { m: inv })
+    }
+
+    // The upper left 3x3 block.
+    pub fn linear(&self) -> Mat3 {
+        let m = &self.m;
+        Mat3::from([
+            [m[0][0], m[0][1], m[0][2]],
+            [m[1][0], m[1][1], m[1][2]],
+            [m[2][0], m[2][1], m[2][2]],
+        ])
+    }
+
+    pub fn offset(&self) -> Vec3 {
+        Vec3::from(self.m[0][3], self.m[1][3], self.m[2][3])
+    }
+
+    // See 'Mat3::normal_matrix'.
+    pub fn normal_matrix(&self) -> Option<Mat3> {
+        self.linear().normal_matrix()
+    }
+
+    pub fn transform_point(&self, p: &Point3) -> Point3 {
+        let m = &self.m;
+        let x = m[0][0] * p.x() + m[0][1] * p.y() + m[0][2] * p.z() + m[0][3];
+        let y = m[1][0] * p.x() + m[1][1] * p.y() + m[1][2] * p.z() + m[1][3];
+        let z = m[2][0] * p.x() + m[2][1] * p.y() + m[2][2] * p.z() + m[2][3];
+        let w = m[3][0] * p.x() + m[3][1] * p.y() + m[3][2] * p.z() + m[3][3];
+        if w == 1.0 { Point3::from(x, y, z) } else { Point3::from(x, y, z) / w }
+    }
+
+    // Directions ignore the translation.
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::from(
+            m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+            m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+            m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z(),
+        )
+    }
+
+    // Normals are not directions: they transform by the inverse transpose, and are renormalized.
+    // Use 'normal_matrix' instead when transforming many normals by the same matrix.
+    pub fn transform_normal(&self, n: &Vec3) -> Vec3 {
+        match self.normal_matrix() {
+            Some(normal_matrix) => (normal_matrix * *n).unit(),
+            None => *n,
+        }
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    // 'a * b' applies b first, then a.
+    fn mul(self, other: Mat4) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        Mat4 { m }
+    }
+}
@@ -1,7 +1,10 @@
+use std::sync::Arc;
+
 use crate::common::*;
 use crate::hittable::HitRecord;
-
-use rand::Rng;
+use crate::microfacet::{ self, Charlie, TrowbridgeReitz };
+use crate::spectrum::{ self, Ior };
+use crate::texture::{ self, Texture };
 
 pub trait Material {
     fn scatter(
@@ -9,6 +12,125 @@ pub trait Material {
         r_in: &Ray,
         hit_record: &HitRecord,
     ) -> Option<(Color, Ray)>;
+
+    // Radiance emitted back along 'r_in' at the hit point.
+    fn emitted(
+        &self,
+        _r_in: &Ray,
+        _hit_record: &HitRecord,
+    ) -> Color {
+        Color::new()
+    }
+
+    // BSDF times |cos| between 'scattered' and the normal.
+    // For non-specular materials, 'scatter' returns eval / pdf as its attenuation.
+    fn eval(
+        &self,
+        _r_in: &Ray,
+        _hit_record: &HitRecord,
+        _scattered: &Ray,
+    ) -> Color {
+        Color::new()
+    }
+
+    // Solid angle density with which 'scatter' produces 'scattered'.
+    fn pdf(
+        &self,
+        _r_in: &Ray,
+        _hit_record: &HitRecord,
+        _scattered: &Ray,
+    ) -> f64 {
+        0.0
+    }
+
+    // Ratio between the attenuation of importance (traced from emitters) and of radiance
+    // along 'scattered'. Refraction does not rescale radiance here, so its adjoint must.
+    fn importance_scale(
+        &self,
+        _r_in: &Ray,
+        _hit_record: &HitRecord,
+        _scattered: &Ray,
+    ) -> f64 {
+        1.0
+    }
+
+    // Phase functions of participating media scatter at points inside a volume. There is no
+    // surface there, so no cosine factor applies and the normal of the hit record is meaningless.
+    fn is_volumetric(&self) -> bool {
+        false
+    }
+
+    // Specular materials can only be sampled, so 'eval' and 'pdf' are meaningless for them
+    // and bidirectional connections must not end on them.
+    fn is_specular(&self, _hit_record: &HitRecord) -> bool {
+        true
+    }
+
+    // Absorption per unit distance of the medium inside the closed surface, for dielectrics
+    // (see 'medium_towards'). None for surfaces with no inside to enter.
+    fn interior(&self) -> Option<Color> {
+        None
+    }
+}
+
+// === Interior Media ===
+
+// Dielectrics enclose a medium that absorbs light along every segment of a path inside them,
+// wherever the segment ends: at the surface, at an emitter or another object inside (a liquid
+// in a glass), or at a bidirectional connection. Rays carry the medium they travel through,
+// entered at the surface of a dielectric. After leaving one it is unknown until the ray reaches
+// the inside of another dielectric, and is otherwise clear. Absorption weighs paths down as they
+// go rather than stopping them at collisions, so it takes no bounces.
+
+// Absorption coefficient with which white light becomes 'color' over 'distance'.
+fn absorption_coefficient(color: &Color, distance: f64) -> Color {
+    let coefficient = |c: f64| -c.clamp(1e-6, 1.0).ln() / distance.max(1e-6);
+    Color::from(coefficient(color.x()), coefficient(color.y()), coefficient(color.z()))
+}
+
+// Medium on the side of the surface at 'hit_record' that 'direction' points to, for a path that
+// reached it through 'medium' on the side that 'incoming' points to. None if unknown.
+pub fn medium_towards(medium: Option<Color>, hit_record: &HitRecord, incoming: &Vec3, direction: &Vec3) -> Option<Color> {
+    let Some(interior) = hit_record.material.interior() else {
+        return medium; // Nothing to cross into
+    };
+    let outward = if hit_record.front_face { hit_record.normal } else { -hit_record.normal };
+    if direction.dot(&outward) < 0.0 {
+        Some(interior)
+    } else if incoming.dot(&outward) > 0.0 {
+        medium
+    } else {
+        None
+    }
+}
+
+// Medium that 'ray' travelled through to reach 'hit_record'.
+pub fn arrival_medium(ray: &Ray, hit_record: &HitRecord) -> Option<Color> {
+    let back = -ray.direction();
+    ray.medium().or_else(|| medium_towards(None, hit_record, &back, &back))
+}
+
+// 'scattered' at 'hit_record' by 'r_in', in the medium it leaves into.
+pub fn scattered_in_medium(r_in: &Ray, hit_record: &HitRecord, scattered: Ray) -> Ray {
+    let medium = medium_towards(arrival_medium(r_in, hit_record), hit_record, &-r_in.direction(), &scattered.direction());
+    scattered.in_medium(medium)
+}
+
+// Fraction of light left after 'distance' through 'medium', at the wavelengths of the sample.
+pub fn medium_transmittance(medium: Option<Color>, distance: f64) -> Color {
+    match medium {
+        Some(absorption) if !absorption.is_zero() => {
+            let absorption = spectrum::upsample(&absorption);
+            let transmittance = |a: f64| (-a.max(0.0) * distance).exp();
+            Color::from(transmittance(absorption.x()), transmittance(absorption.y()), transmittance(absorption.z()))
+        }
+        _ => Color::from(1.0, 1.0, 1.0),
+    }
+}
+
+// Fraction of the light carried by 'ray' that reaches 'hit_record'.
+pub fn arrival_transmittance(ray: &Ray, hit_record: &HitRecord) -> Color {
+    medium_transmittance(arrival_medium(ray, hit_record), hit_record.t * ray.direction().abs())
 }
 
 // === Lambertian (Diffuse) ===
@@ -30,9 +152,88 @@ impl Material for Lambertian {
     // 'Color' means attenuation
     // 'Ray' means scattered ray
     fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+    ) -> Option<(Color, Ray)> {
+        let mut scatter_direction = hit_record.normal + Vec3::random_unit_vector();
+
+        if scatter_direction.is_zero() {
+            scatter_direction = hit_record.normal;
+        }
+
+        Some((
+            spectrum::upsample(&self.albedo),
+            Ray::from_time(hit_record.point, scatter_direction, r_in.time())
+        ))
+    }
+
+    fn eval(
+        &self,
+        _r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> Color {
+        let cosine = hit_record.normal.dot(&scattered.direction().unit());
+        if cosine > 0.0 {
+            spectrum::upsample(&self.albedo) * (cosine / PI)
+        } else {
+            Color::new()
+        }
+    }
+
+    fn pdf(
         &self,
         _r_in: &Ray,
         hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> f64 {
+        let cosine = hit_record.normal.dot(&scattered.direction().unit());
+        if cosine > 0.0 { cosine / PI } else { 0.0 }
+    }
+
+    fn is_specular(&self, _hit_record: &HitRecord) -> bool {
+        false
+    }
+}
+
+// === Oren-Nayar (Rough Diffuse) ===
+
+// Diffuse surface made of V-shaped grooves, each a Lambertian facet, with normals spread by
+// 'sigma' (degrees). The grooves reflect light back towards where it came from and look flatter
+// than Lambertian, like clay, concrete or the full moon. Sigma 0 is Lambertian.
+pub struct OrenNayar {
+    albedo: Color,
+    a: f64,
+    b: f64,
+}
+
+#[allow(dead_code)]
+impl OrenNayar {
+    pub fn from(color: &Color, sigma: f64) -> OrenNayar {
+        let sigma2 = degrees_to_radians(sigma).powi(2);
+        OrenNayar {
+            albedo: *color,
+            a: 1.0 - sigma2 / (2.0 * (sigma2 + 0.33)),
+            b: 0.45 * sigma2 / (sigma2 + 0.09),
+        }
+    }
+
+    // BSDF over albedo / PI, for unit 'wo' and 'wi' above the surface.
+    // max(0, cos(phi_i - phi_o)) sin(alpha) tan(beta) simplifies to the projection of 'wi' on
+    // the surface dotted with that of 'wo', over the larger cosine of the two.
+    fn factor(&self, normal: &Vec3, wo: &Vec3, wi: &Vec3) -> f64 {
+        let (cos_o, cos_i) = (wo.dot(normal), wi.dot(normal));
+        let tangential = (wo.dot(wi) - cos_o * cos_i).max(0.0);
+        self.a + self.b * tangential / cos_o.max(cos_i).max(EPS)
+    }
+}
+
+impl Material for OrenNayar {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
     ) -> Option<(Color, Ray)> {
         let mut scatter_direction = hit_record.normal + Vec3::random_unit_vector();
 
@@ -40,11 +241,162 @@ impl Material for Lambertian {
             scatter_direction = hit_record.normal;
         }
 
+        // Cosine weighted, so f * cos / pdf leaves albedo * factor.
+        let wo = -r_in.direction().unit();
+        let factor = self.factor(&hit_record.normal, &wo, &scatter_direction.unit());
         Some((
-            self.albedo,
-            Ray::from(hit_record.point, scatter_direction)
+            spectrum::upsample(&self.albedo) * factor,
+            Ray::from_time(hit_record.point, scatter_direction, r_in.time())
         ))
     }
+
+    fn eval(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> Color {
+        let wo = -r_in.direction().unit();
+        let wi = scattered.direction().unit();
+        let cosine = hit_record.normal.dot(&wi);
+        if cosine > 0.0 && hit_record.normal.dot(&wo) > 0.0 {
+            spectrum::upsample(&self.albedo) * (self.factor(&hit_record.normal, &wo, &wi) * cosine / PI)
+        } else {
+            Color::new()
+        }
+    }
+
+    fn pdf(
+        &self,
+        _r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> f64 {
+        let cosine = hit_record.normal.dot(&scattered.direction().unit());
+        if cosine > 0.0 { cosine / PI } else { 0.0 }
+    }
+
+    fn is_specular(&self, _hit_record: &HitRecord) -> bool {
+        false
+    }
+}
+
+// === Velvet (Sheen) ===
+
+// Cloth: a Lambertian base under a sheen lobe, the soft bright rim that fibres standing up
+// from the surface give at grazing angles. Directions are sampled from either lobe, in
+// proportion to their colours.
+pub struct Velvet {
+    albedo: Color,
+    sheen: Color,
+    distribution: Charlie,
+    sheen_albedo: Vec<f64>, // Of the lobe, at cosines evenly spaced over [0, 1]
+}
+
+const SHEEN_ALBEDO_SIZE: usize = 16;
+
+#[allow(dead_code)]
+impl Velvet {
+    // 'roughness' in (0, 1] spreads the rim, from a thin halo to a soft glow over the surface.
+    pub fn from(color: &Color, sheen: &Color, roughness: f64) -> Velvet {
+        let distribution = Charlie::from(roughness);
+        Velvet {
+            albedo: *color,
+            sheen: *sheen,
+            distribution,
+            sheen_albedo: (0..SHEEN_ALBEDO_SIZE)
+                .map(|i| distribution.albedo(i as f64 / (SHEEN_ALBEDO_SIZE - 1) as f64))
+                .collect(),
+        }
+    }
+
+    fn sheen_albedo(&self, cos_theta: f64) -> f64 {
+        let x = cos_theta.clamp(0.0, 1.0) * (SHEEN_ALBEDO_SIZE - 1) as f64;
+        let i = (x as usize).min(SHEEN_ALBEDO_SIZE - 2);
+        let t = x - i as f64;
+        self.sheen_albedo[i] * (1.0 - t) + self.sheen_albedo[i + 1] * t
+    }
+
+    // Probability of sampling the sheen lobe rather than the base.
+    fn sheen_probability(&self) -> f64 {
+        let (base, sheen) = (luminance(self.albedo), luminance(self.sheen));
+        if base + sheen > 0.0 { sheen / (base + sheen) } else { 0.5 }
+    }
+
+    // BSDF * cos and the density of sampling 'wi', in the shading frame.
+    fn eval_local(&self, wo: &Vec3, wi: &Vec3) -> (Color, f64) {
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return (Color::new(), 0.0);
+        }
+        let wm = (*wo + *wi).unit();
+        let sheen = spectrum::upsample(&self.sheen);
+        let lobe = self.distribution.d(&wm) * self.distribution.visibility(wo, wi);
+
+        // The base gets the light the sheen does not reflect. Taking the larger albedo of the
+        // two directions keeps that reciprocal, and energy conserving.
+        let albedo = self.sheen_albedo(wo.z()).max(self.sheen_albedo(wi.z()));
+        let base = spectrum::upsample(&self.albedo) * (Color::from(1.0, 1.0, 1.0) - sheen * albedo);
+        let f = base / PI + sheen * lobe;
+
+        let p = self.sheen_probability();
+        let pdf = (1.0 - p) * wi.z() / PI + p * self.distribution.pdf(&wm) / (4.0 * wo.dot(&wm));
+        (f * wi.z(), pdf)
+    }
+}
+
+impl Material for Velvet {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+    ) -> Option<(Color, Ray)> {
+        let frame = Onb::from_w(&hit_record.normal);
+        let wo = frame.to_local(&-r_in.direction().unit());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let up = Vec3::from(0.0, 0.0, 1.0);
+        let wi = if random_double() < self.sheen_probability() {
+            microfacet::reflect(&wo, &self.distribution.sample_wm())
+        } else {
+            let direction = up + Vec3::random_unit_vector();
+            if direction.is_zero() { up } else { direction.unit() }
+        };
+        let (f, pdf) = self.eval_local(&wo, &wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some((f / pdf, Ray::from_time(hit_record.point, frame.local(&wi), r_in.time())))
+    }
+
+    fn eval(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> Color {
+        let frame = Onb::from_w(&hit_record.normal);
+        let wo = frame.to_local(&-r_in.direction().unit());
+        let wi = frame.to_local(&scattered.direction().unit());
+        self.eval_local(&wo, &wi).0
+    }
+
+    fn pdf(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> f64 {
+        let frame = Onb::from_w(&hit_record.normal);
+        let wo = frame.to_local(&-r_in.direction().unit());
+        let wi = frame.to_local(&scattered.direction().unit());
+        self.eval_local(&wo, &wi).1
+    }
+
+    fn is_specular(&self, _hit_record: &HitRecord) -> bool {
+        false
+    }
 }
 
 // === Metal (Mirror Reflection) ===
@@ -70,11 +422,11 @@ impl Material for Metal {
         hit_record: &HitRecord,
     ) -> Option<(Color, Ray)> {
         let reflected = Vec3::reflect(r_in.direction().unit(), hit_record.normal);
-        let scattered = Ray::from(hit_record.point, reflected + self.fuzz * Vec3::random_unit_vector());
+        let scattered = Ray::from_time(hit_record.point, reflected + self.fuzz * Vec3::random_unit_vector(), r_in.time());
         
         if scattered.direction().dot(&hit_record.normal) > 0.0 {
             Some((
-                self.albedo,
+                spectrum::upsample(&self.albedo),
                 scattered
             ))
         } else {
@@ -83,23 +435,182 @@ impl Material for Metal {
     }
 }
 
+// === Conductor (Microfacet Metal) ===
+
+// Rough metal with a Trowbridge-Reitz (GGX) distribution of microfacets and the exact Fresnel
+// reflectance of a complex index of refraction eta + ik per channel. Reflections are sampled
+// from the visible microfacet normals, so 'scatter' weights stay close to the reflectance.
+pub struct Conductor {
+    eta: Color,
+    k: Color,
+    distribution: TrowbridgeReitz,
+    tangent: Option<Vec3>, // Along the x roughness on the surface; None for isotropic roughness
+}
+
+// Measured eta and k at the red, green and blue wavelengths of common metals.
+#[derive(Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+pub enum ConductorPreset {
+    Gold,
+    Copper,
+    Aluminium,
+    Silver,
+}
+
+impl ConductorPreset {
+    // (eta, k)
+    pub fn ior(self) -> (Color, Color) {
+        match self {
+            ConductorPreset::Gold => (Color::from(0.143119, 0.374957, 1.44248), Color::from(3.98316, 2.38572, 1.60322)),
+            ConductorPreset::Copper => (Color::from(0.200438, 0.924033, 1.10221), Color::from(3.91295, 2.45285, 2.14219)),
+            ConductorPreset::Aluminium => (Color::from(1.65746, 0.880369, 0.521229), Color::from(9.22387, 6.26952, 4.837)),
+            ConductorPreset::Silver => (Color::from(0.155265, 0.116723, 0.138342), Color::from(4.82835, 3.12225, 2.14696)),
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Conductor {
+    // 'roughness' in [0, 1], 0 for a perfect mirror.
+    pub fn from(eta: &Color, k: &Color, roughness: f64) -> Conductor {
+        Conductor {
+            eta: *eta,
+            k: *k,
+            distribution: TrowbridgeReitz::from(roughness, roughness),
+            tangent: None,
+        }
+    }
+
+    pub fn preset(preset: ConductorPreset, roughness: f64) -> Conductor {
+        let (eta, k) = preset.ior();
+        Conductor::from(&eta, &k, roughness)
+    }
+
+    // Brushed metal: 'roughness_x' along 'tangent' (a world direction, projected onto the
+    // surface) and 'roughness_y' across it.
+    pub fn anisotropic(eta: &Color, k: &Color, roughness_x: f64, roughness_y: f64, tangent: &Vec3) -> Conductor {
+        Conductor {
+            eta: *eta,
+            k: *k,
+            distribution: TrowbridgeReitz::from(roughness_x, roughness_y),
+            tangent: Some(*tangent),
+        }
+    }
+
+    // Shading frame with the normal along z.
+    fn frame(&self, hit_record: &HitRecord) -> Onb {
+        match self.tangent {
+            Some(tangent) => Onb::from_wu(&hit_record.normal, &tangent),
+            None => Onb::from_w(&hit_record.normal),
+        }
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+    ) -> Option<(Color, Ray)> {
+        let frame = self.frame(hit_record);
+        let wo = frame.to_local(&-r_in.direction().unit());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        if self.distribution.effectively_smooth() {
+            let wi = Vec3::from(-wo.x(), -wo.y(), wo.z());
+            let attenuation = spectrum::upsample(&microfacet::fresnel_conductor(wo.z(), &self.eta, &self.k));
+            return Some((attenuation, Ray::from_time(hit_record.point, frame.local(&wi), r_in.time())));
+        }
+
+        let wm = self.distribution.sample_wm(&wo);
+        let wi = microfacet::reflect(&wo, &wm);
+        if wi.z() <= 0.0 {
+            return None; // Reflected into the surface by a masked microfacet
+        }
+
+        // f * cos / pdf = D F G / (4 cos_o cos_i) * cos_i / (D G1 / (4 cos_o)).
+        let fresnel = microfacet::fresnel_conductor(wo.dot(&wm), &self.eta, &self.k);
+        let attenuation = spectrum::upsample(&fresnel) * (self.distribution.g(&wo, &wi) / self.distribution.g1(&wo));
+        Some((attenuation, Ray::from_time(hit_record.point, frame.local(&wi), r_in.time())))
+    }
+
+    fn eval(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> Color {
+        let frame = self.frame(hit_record);
+        let wo = frame.to_local(&-r_in.direction().unit());
+        let wi = frame.to_local(&scattered.direction().unit());
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return Color::new();
+        }
+        let wm = (wo + wi).unit();
+        let fresnel = microfacet::fresnel_conductor(wo.dot(&wm), &self.eta, &self.k);
+        spectrum::upsample(&fresnel) * (self.distribution.d(&wm) * self.distribution.g(&wo, &wi) / (4.0 * wo.z()))
+    }
+
+    fn pdf(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> f64 {
+        let frame = self.frame(hit_record);
+        let wo = frame.to_local(&-r_in.direction().unit());
+        let wi = frame.to_local(&scattered.direction().unit());
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return 0.0;
+        }
+        let wm = (wo + wi).unit();
+        self.distribution.pdf(&wo, &wm) / (4.0 * wo.dot(&wm))
+    }
+
+    fn is_specular(&self, _hit_record: &HitRecord) -> bool {
+        self.distribution.effectively_smooth()
+    }
+}
+
 // === Dielectric ===
 
 pub struct Dielectric {
-    index_of_refraction: f64,
+    index_of_refraction: Ior,
+    absorption: Color, // Per unit distance inside
 }
 
 impl Dielectric {
     pub fn from(index_of_refraction: f64) -> Dielectric {
         Dielectric {
-            index_of_refraction
+            index_of_refraction: Ior::Constant(index_of_refraction),
+            absorption: Color::new(),
+        }
+    }
+
+    // Tinted glass or a coloured liquid, through which white light takes on 'color' after
+    // travelling 'distance' inside.
+    #[allow(dead_code)]
+    pub fn tinted(index_of_refraction: f64, color: &Color, distance: f64) -> Dielectric {
+        Dielectric {
+            index_of_refraction: Ior::Constant(index_of_refraction),
+            absorption: absorption_coefficient(color, distance),
+        }
+    }
+
+    // Glass that splits white light into colours in spectral mode.
+    #[allow(dead_code)]
+    pub fn dispersive(index_of_refraction: Ior) -> Dielectric {
+        Dielectric {
+            index_of_refraction,
+            absorption: Color::new(),
         }
     }
-    fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
-        // Use Schlick's approximation for reflectance.
-        let r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
-        let r0 = r0 * r0;
-        r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+
+    fn reflectance(cosine: f64, refraction_ratio: f64) -> f64 {
+        // The exact Fresnel equations, 'refraction_ratio' being the incident over the transmitted index.
+        microfacet::fresnel_dielectric(cosine, 1.0 / refraction_ratio)
     }
 }
 
@@ -109,7 +620,8 @@ impl Material for Dielectric {
         r_in: &Ray,
         hit_record: &HitRecord,
     ) -> Option<(Color, Ray)> {
-        let refraction_ratio = if hit_record.front_face { 1.0 / self.index_of_refraction } else { self.index_of_refraction };
+        let index_of_refraction = self.index_of_refraction.current();
+        let refraction_ratio = if hit_record.front_face { 1.0 / index_of_refraction } else { index_of_refraction };
         let r = r_in.direction().unit();
 
         let cos_theta = (-r).dot(&hit_record.normal).min(1.0);
@@ -117,16 +629,778 @@ impl Material for Dielectric {
 
         let can_refract = refraction_ratio * sin_theta <= 1.0;
 
-        let refracted = if can_refract && Dielectric::reflectance(cos_theta, refraction_ratio) <= rand::thread_rng().gen_range(0.0..1.0) {
+        let refracted = if can_refract && Dielectric::reflectance(cos_theta, refraction_ratio) <= random_double() {
             Vec3::refract(r, hit_record.normal, refraction_ratio)
         } else {
             Vec3::reflect(r, hit_record.normal)
         };
 
-        return Some((
-            Color::from(1.0, 1.0, 1.0),
-            Ray::from(hit_record.point, refracted),
+        Some((
+            self.index_of_refraction.mask(),
+            Ray::from_time(hit_record.point, refracted, r_in.time()),
         ))
     }
 
+    fn importance_scale(
+        &self,
+        _r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> f64 {
+        if scattered.direction().dot(&hit_record.normal) < 0.0 {
+            let index_of_refraction = self.index_of_refraction.current();
+            let refraction_ratio = if hit_record.front_face { 1.0 / index_of_refraction } else { index_of_refraction };
+            refraction_ratio * refraction_ratio
+        } else {
+            1.0
+        }
+    }
+
+    fn interior(&self) -> Option<Color> {
+        Some(self.absorption)
+    }
+}
+
+// === Rough Dielectric (Frosted Glass) ===
+
+// Glass with a Trowbridge-Reitz (GGX) distribution of microfacets that reflect and refract
+// (Walter et al. 2007), with the exact Fresnel equations. Like 'Dielectric', refraction does
+// not rescale radiance. Roughness 0 is a perfectly smooth window.
+pub struct RoughDielectric {
+    index_of_refraction: Ior,
+    distribution: TrowbridgeReitz,
+    absorption: Color, // Per unit distance inside
+}
+
+#[allow(dead_code)]
+impl RoughDielectric {
+    pub fn from(index_of_refraction: f64, roughness: f64) -> RoughDielectric {
+        RoughDielectric {
+            index_of_refraction: Ior::Constant(index_of_refraction),
+            distribution: TrowbridgeReitz::from(roughness, roughness),
+            absorption: Color::new(),
+        }
+    }
+
+    // Frosted tinted glass, see 'Dielectric::tinted'.
+    pub fn tinted(index_of_refraction: f64, roughness: f64, color: &Color, distance: f64) -> RoughDielectric {
+        RoughDielectric {
+            index_of_refraction: Ior::Constant(index_of_refraction),
+            distribution: TrowbridgeReitz::from(roughness, roughness),
+            absorption: absorption_coefficient(color, distance),
+        }
+    }
+
+    pub fn dispersive(index_of_refraction: Ior, roughness: f64) -> RoughDielectric {
+        RoughDielectric {
+            index_of_refraction,
+            distribution: TrowbridgeReitz::from(roughness, roughness),
+            absorption: Color::new(),
+        }
+    }
+
+    // Shading frame with the normal on the side 'r_in' arrives from, and the index of refraction
+    // on the far side over the one on that side. Bidirectional methods evaluate the reverse
+    // direction with the same hit record, so the side of the record may be the wrong one.
+    fn frame(&self, r_in: &Ray, hit_record: &HitRecord) -> (Onb, f64) {
+        let flipped = r_in.direction().dot(&hit_record.normal) > 0.0;
+        let normal = if flipped { -hit_record.normal } else { hit_record.normal };
+        let index_of_refraction = self.index_of_refraction.current();
+        let eta = if hit_record.front_face != flipped { index_of_refraction } else { 1.0 / index_of_refraction };
+        (Onb::from_w(&normal), eta)
+    }
+
+    // The microfacet normal between 'wo' and 'wi' in the shading frame, facing up, and whether
+    // 'wi' is a reflection. None if the microfacet would face away from either direction.
+    fn half_vector(&self, wo: &Vec3, wi: &Vec3, eta: f64) -> Option<(Vec3, bool)> {
+        let reflect = wi.z() > 0.0;
+        let wm = if reflect { *wo + *wi } else { *wi * eta + *wo };
+        if wo.z() <= 0.0 || wi.z() == 0.0 || wm.is_zero() {
+            return None;
+        }
+        let wm = wm.unit();
+        let wm = if wm.z() < 0.0 { -wm } else { wm };
+        if wm.dot(wi) * wi.z() < 0.0 || wm.dot(wo) * wo.z() < 0.0 {
+            return None;
+        }
+        Some((wm, reflect))
+    }
+
+    // BSDF times |cos| of 'wi' in a shading frame with the normal along z, and 'eta' the index
+    // of refraction below the surface over the one above. 'wo' may be on either side.
+    fn f_local(&self, wo: &Vec3, wi: &Vec3, eta: f64) -> f64 {
+        if wo.z() < 0.0 {
+            return self.f_local(&-*wo, &-*wi, 1.0 / eta);
+        }
+        let Some((wm, reflect)) = self.half_vector(wo, wi, eta) else {
+            return 0.0;
+        };
+
+        let reflectance = microfacet::fresnel_dielectric(wo.dot(&wm), eta);
+        let d_g = self.distribution.d(&wm) * self.distribution.g(wo, wi);
+        if reflect {
+            d_g * reflectance / (4.0 * wo.z())
+        } else {
+            let denominator = wi.dot(&wm) + wo.dot(&wm) / eta;
+            d_g * (1.0 - reflectance) * (wi.dot(&wm) * wo.dot(&wm)).abs() / (wo.z() * denominator * denominator)
+        }
+    }
+
+    // Density with which 'sample_local' produces 'wi'.
+    fn pdf_local(&self, wo: &Vec3, wi: &Vec3, eta: f64) -> f64 {
+        if wo.z() < 0.0 {
+            return self.pdf_local(&-*wo, &-*wi, 1.0 / eta);
+        }
+        let Some((wm, reflect)) = self.half_vector(wo, wi, eta) else {
+            return 0.0;
+        };
+
+        let reflectance = microfacet::fresnel_dielectric(wo.dot(&wm), eta);
+        let pdf_wm = self.distribution.pdf(wo, &wm);
+        if reflect {
+            pdf_wm / (4.0 * wo.dot(&wm).abs()) * reflectance
+        } else {
+            let denominator = wi.dot(&wm) + wo.dot(&wm) / eta;
+            pdf_wm * wi.dot(&wm).abs() / (denominator * denominator) * (1.0 - reflectance)
+        }
+    }
+
+    // A reflected or refracted 'wi' and its weight, f * |cos| / pdf.
+    fn sample_local(&self, wo: &Vec3, eta: f64) -> Option<(Vec3, f64)> {
+        if wo.z() < 0.0 {
+            return self.sample_local(&-*wo, 1.0 / eta).map(|(wi, weight)| (-wi, weight));
+        }
+        let smooth = self.distribution.effectively_smooth();
+        let wm = if smooth { Vec3::from(0.0, 0.0, 1.0) } else { self.distribution.sample_wm(wo) };
+
+        // Choose between reflection and refraction in proportion to the Fresnel terms, so they
+        // cancel out of the weight.
+        let reflect = random_double() < microfacet::fresnel_dielectric(wo.dot(&wm), eta);
+        let wi = if reflect { microfacet::reflect(wo, &wm) } else { microfacet::refract(wo, &wm, eta)? };
+        if smooth {
+            return Some((wi, 1.0));
+        }
+
+        // Reflected into the surface or refracted out of it by a masked microfacet.
+        if (wi.z() > 0.0) != reflect || wi.z() == 0.0 {
+            return None;
+        }
+        // f * |cos| / pdf = G / G1 for both, as D, F and the Jacobians cancel.
+        Some((wi, self.distribution.g(wo, &wi) / self.distribution.g1(wo)))
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+    ) -> Option<(Color, Ray)> {
+        let (frame, eta) = self.frame(r_in, hit_record);
+        let wo = frame.to_local(&-r_in.direction().unit());
+        let (wi, weight) = self.sample_local(&wo, eta)?;
+        let scattered = Ray::from_time(hit_record.point, frame.local(&wi), r_in.time());
+        Some((self.index_of_refraction.mask() * weight, scattered))
+    }
+
+    fn eval(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> Color {
+        let (frame, eta) = self.frame(r_in, hit_record);
+        let wo = frame.to_local(&-r_in.direction().unit());
+        let wi = frame.to_local(&scattered.direction().unit());
+        self.index_of_refraction.mask() * self.f_local(&wo, &wi, eta)
+    }
+
+    fn pdf(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> f64 {
+        let (frame, eta) = self.frame(r_in, hit_record);
+        let wo = frame.to_local(&-r_in.direction().unit());
+        let wi = frame.to_local(&scattered.direction().unit());
+        self.pdf_local(&wo, &wi, eta)
+    }
+
+    fn importance_scale(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> f64 {
+        let (frame, eta) = self.frame(r_in, hit_record);
+        if scattered.direction().dot(&frame.w()) < 0.0 {
+            1.0 / (eta * eta)
+        } else {
+            1.0
+        }
+    }
+
+    fn is_specular(&self, _hit_record: &HitRecord) -> bool {
+        self.distribution.effectively_smooth()
+    }
+
+    fn interior(&self) -> Option<Color> {
+        Some(self.absorption)
+    }
+}
+
+// === Coated (Layered) ===
+
+// A dielectric clearcoat over any opaque base material, such as car paint over 'Metal' or
+// lacquer over a 'Lambertian'. Light that refracts into the coat bounces between the base and
+// the underside of the coat, which reflects part of it back down, before it leaves. Both layers
+// are infinitely thin and meet at the hit point, and the bounces between them are followed by
+// a random walk (Guo et al. 2018): 'scatter' walks until the light leaves, 'eval' is an unbiased
+// estimate from one walk, and 'pdf' only an approximate one, as it just weights MIS. Over a
+// specular base the whole material is specular, as light could only leave through the coat
+// along the directions that the base happened to sample.
+pub struct Coated {
+    base: Arc<dyn Material + Send + Sync>,
+    coat: RoughDielectric,
+    index_of_refraction: f64,
+    tint: Color, // Transmittance of one pass straight through the coat
+}
+
+// Bounces within the coat before a walk gives up.
+const COATED_MAX_DEPTH: usize = 10;
+
+// Where a walk through a coated material takes place: the shading frame, with the normal on the
+// side 'r_in' arrives from, and the hit record that the base sees, whose normal faces the coat.
+struct CoatedHit {
+    frame: Onb,
+    record: HitRecord,
+    time: f64,
+}
+
+impl CoatedHit {
+    // A ray arriving along local direction 'wo', which points back where it came from.
+    fn ray_in(&self, wo: &Vec3) -> Ray {
+        let direction = self.frame.local(wo);
+        Ray::from_time(self.record.point + direction, -direction, self.time)
+    }
+
+    fn ray_out(&self, wi: &Vec3) -> Ray {
+        Ray::from_time(self.record.point, self.frame.local(wi), self.time)
+    }
+}
+
+#[allow(dead_code)]
+impl Coated {
+    // A clear coat. 'roughness' in [0, 1]; a mirror smooth coat would make the whole material
+    // specular, so it is kept just rough enough to be evaluated.
+    pub fn from(base: Arc<dyn Material + Send + Sync>, index_of_refraction: f64, roughness: f64) -> Coated {
+        Coated::tinted(base, index_of_refraction, roughness, &Color::from(1.0, 1.0, 1.0))
+    }
+
+    // A coloured coat that lets 'tint' through when crossed at normal incidence.
+    pub fn tinted(base: Arc<dyn Material + Send + Sync>, index_of_refraction: f64, roughness: f64, tint: &Color) -> Coated {
+        Coated {
+            base,
+            coat: RoughDielectric::from(index_of_refraction, roughness.max(0.032)),
+            index_of_refraction,
+            tint: *tint,
+        }
+    }
+
+    fn hit(&self, r_in: &Ray, hit_record: &HitRecord) -> CoatedHit {
+        let mut record = hit_record.clone();
+        if r_in.direction().dot(&hit_record.normal) > 0.0 {
+            record.normal = -hit_record.normal;
+        }
+        CoatedHit { frame: Onb::from_w(&record.normal), record, time: r_in.time() }
+    }
+
+    // Transmittance of the coat along 'w'.
+    fn transmittance(&self, w: &Vec3) -> Color {
+        let white = Color::from(1.0, 1.0, 1.0);
+        if (self.tint - white).is_zero() {
+            return white;
+        }
+        let tint = spectrum::upsample(&self.tint);
+        let exponent = 1.0 / w.z().abs().max(EPS);
+        Color::from(tint.x().powf(exponent), tint.y().powf(exponent), tint.z().powf(exponent))
+    }
+
+    // Light arriving at the base from 'wo' scatters back up into the coat along the returned
+    // direction, or None if the base absorbs or transmits it.
+    fn base_scatter(&self, hit: &CoatedHit, wo: &Vec3) -> Option<(Color, Vec3)> {
+        let (attenuation, scattered) = self.base.scatter(&hit.ray_in(wo), &hit.record)?;
+        let wi = hit.frame.to_local(&scattered.direction().unit());
+        if wi.z() > 0.0 { Some((attenuation, wi)) } else { None }
+    }
+
+    fn base_eval(&self, hit: &CoatedHit, wo: &Vec3, wi: &Vec3) -> Color {
+        self.base.eval(&hit.ray_in(wo), &hit.record, &hit.ray_out(wi))
+    }
+
+    fn base_pdf(&self, hit: &CoatedHit, wo: &Vec3, wi: &Vec3) -> f64 {
+        self.base.pdf(&hit.ray_in(wo), &hit.record, &hit.ray_out(wi))
+    }
+
+    // A direction from the base that leaves the coat along 'wi', sampled from the coat above.
+    fn sample_exit(&self, wi: &Vec3) -> Option<Vec3> {
+        let (u, _) = self.coat.sample_local(wi, self.index_of_refraction)?;
+        if u.z() < 0.0 { Some(-u) } else { None }
+    }
+}
+
+impl Material for Coated {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+    ) -> Option<(Color, Ray)> {
+        let hit = self.hit(r_in, hit_record);
+        let eta = self.index_of_refraction;
+        let wo = hit.frame.to_local(&-r_in.direction().unit());
+        let (mut w, weight) = self.coat.sample_local(&wo, eta)?;
+        let mut beta = Color::from(weight, weight, weight);
+
+        for depth in 0..COATED_MAX_DEPTH {
+            if w.z() > 0.0 {
+                return Some((beta, hit.ray_out(&w)));
+            }
+            // Down to the base, back up, and off the underside of the coat.
+            let (attenuation, up) = self.base_scatter(&hit, &-w)?;
+            beta = beta * self.transmittance(&w) * attenuation * self.transmittance(&up);
+            let (next, weight) = self.coat.sample_local(&-up, eta)?;
+            beta *= weight;
+            w = next;
+            if !survives_roulette(&mut beta, depth) {
+                return None;
+            }
+        }
+        None
+    }
+
+    fn eval(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> Color {
+        let hit = self.hit(r_in, hit_record);
+        let eta = self.index_of_refraction;
+        let wo = hit.frame.to_local(&-r_in.direction().unit());
+        let wi = hit.frame.to_local(&scattered.direction().unit());
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return Color::new();
+        }
+
+        let reflected = self.coat.f_local(&wo, &wi, eta);
+        let mut f = Color::from(reflected, reflected, reflected);
+        let Some((mut w, weight)) = self.coat.sample_local(&wo, eta) else {
+            return f;
+        };
+        let mut beta = Color::from(weight, weight, weight);
+        let exit = self.sample_exit(&wi);
+
+        // Each time the walk reaches the base it connects to 'wi', through the exit direction
+        // sampled from the coat and through the direction sampled from the base, which are
+        // combined with the power heuristic.
+        for depth in 0..COATED_MAX_DEPTH {
+            if w.z() > 0.0 {
+                break; // Left the coat, which the connections account for
+            }
+            let wo_base = -w;
+            beta = beta * self.transmittance(&w);
+            if let Some(v) = exit {
+                let pdf_exit = self.coat.pdf_local(&wi, &-v, eta);
+                if pdf_exit > 0.0 {
+                    let weight = power_heuristic(pdf_exit, self.base_pdf(&hit, &wo_base, &v));
+                    let f_exit = self.coat.f_local(&-v, &wi, eta);
+                    f += beta * self.base_eval(&hit, &wo_base, &v) * self.transmittance(&v) * (f_exit * weight / pdf_exit);
+                }
+            }
+
+            let Some((attenuation, up)) = self.base_scatter(&hit, &wo_base) else {
+                break;
+            };
+            beta = beta * attenuation * self.transmittance(&up);
+            let f_exit = self.coat.f_local(&-up, &wi, eta);
+            if f_exit > 0.0 {
+                let weight = power_heuristic(self.base_pdf(&hit, &wo_base, &up), self.coat.pdf_local(&wi, &-up, eta));
+                f += beta * (f_exit * weight);
+            }
+
+            let Some((next, weight)) = self.coat.sample_local(&-up, eta) else {
+                break;
+            };
+            beta *= weight;
+            w = next;
+            if !survives_roulette(&mut beta, depth) {
+                break;
+            }
+        }
+        f
+    }
+
+    fn pdf(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> f64 {
+        let hit = self.hit(r_in, hit_record);
+        let eta = self.index_of_refraction;
+        let wo = hit.frame.to_local(&-r_in.direction().unit());
+        let wi = hit.frame.to_local(&scattered.direction().unit());
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return 0.0;
+        }
+
+        // Reflection off the coat, and a single bounce off the base estimated like 'eval'.
+        let mut pdf = self.coat.pdf_local(&wo, &wi, eta);
+        if let Some((w, _)) = self.coat.sample_local(&wo, eta).filter(|(w, _)| w.z() < 0.0) {
+            let wo_base = -w;
+            if let Some(v) = self.sample_exit(&wi) {
+                let pdf_exit = self.coat.pdf_local(&wi, &-v, eta);
+                let pdf_base = self.base_pdf(&hit, &wo_base, &v);
+                if pdf_exit > 0.0 {
+                    pdf += power_heuristic(pdf_exit, pdf_base) * pdf_base * self.coat.pdf_local(&-v, &wi, eta) / pdf_exit;
+                }
+            }
+            if let Some((_, up)) = self.base_scatter(&hit, &wo_base) {
+                let weight = power_heuristic(self.base_pdf(&hit, &wo_base, &up), self.coat.pdf_local(&wi, &-up, eta));
+                pdf += weight * self.coat.pdf_local(&-up, &wi, eta);
+            }
+        }
+
+        // Mixed with a uniform density, so that MIS never relies on the estimate alone.
+        0.9 * pdf + 0.1 / (2.0 * PI)
+    }
+
+    fn is_specular(&self, hit_record: &HitRecord) -> bool {
+        self.base.is_specular(hit_record)
+    }
+}
+
+fn power_heuristic(pdf_f: f64, pdf_g: f64) -> f64 {
+    let (f2, g2) = (pdf_f * pdf_f, pdf_g * pdf_g);
+    if f2 + g2 > 0.0 { f2 / (f2 + g2) } else { 0.0 }
+}
+
+// Russian roulette for walks whose throughput has become small; false if the walk ends.
+fn survives_roulette(beta: &mut Color, depth: usize) -> bool {
+    let max = beta.x().max(beta.y()).max(beta.z());
+    if depth < 3 || max >= 0.25 {
+        return true;
+    }
+    let q = 1.0 - max;
+    if random_double() < q {
+        return false;
+    }
+    *beta /= 1.0 - q;
+    true
+}
+
+// === Thin Film (Iridescence) ===
+
+// A smooth dielectric film a few hundred nanometres thick over a substrate, like a soap bubble,
+// oil on water or the oxide on heated metal. The light reflected off its two sides interferes,
+// so its reflectance changes with wavelength, angle and thickness. Spectral mode evaluates it at
+// the wavelengths of each sample; RGB mode uses its colour under white light instead.
+pub struct ThinFilm {
+    film_ior: f64,
+    thickness: Arc<dyn Texture + Send + Sync>, // In [0, 1] from 'thickness_range'
+    thickness_range: (f64, f64),               // nm
+    substrate: Substrate,
+}
+
+// What lies under the film.
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub enum Substrate {
+    Air,                                 // A free standing film, which light passes straight through
+    Dielectric(f64),                     // A transparent body with this index of refraction
+    Conductor { eta: Color, k: Color },  // A metal, with eta and k at red, green and blue (see 'ConductorPreset')
+}
+
+#[allow(dead_code)]
+impl Substrate {
+    pub fn metal(preset: ConductorPreset) -> Substrate {
+        let (eta, k) = preset.ior();
+        Substrate::Conductor { eta, k }
+    }
+}
+
+// Wavelengths (nm) that the red, green and blue indices of refraction of conductors are taken at.
+const CHANNEL_WAVELENGTHS: [f64; 3] = [630.0, 532.0, 465.0];
+
+#[allow(dead_code)]
+impl ThinFilm {
+    // A film 'thickness' nm thick everywhere.
+    pub fn from(film_ior: f64, thickness: f64, substrate: Substrate) -> ThinFilm {
+        ThinFilm::textured(film_ior, texture::constant(1.0), (thickness, thickness), substrate)
+    }
+
+    // A film from 'range.0' nm thick where 'thickness' is black to 'range.1' where it is white.
+    pub fn textured(film_ior: f64, thickness: Arc<dyn Texture + Send + Sync>, range: (f64, f64), substrate: Substrate) -> ThinFilm {
+        ThinFilm {
+            film_ior,
+            thickness,
+            thickness_range: range,
+            substrate,
+        }
+    }
+
+    // Index of refraction on the side light arrives from, and complex index on the other side.
+    fn media(&self, front_face: bool, wavelength: f64) -> (f64, f64, f64) {
+        match self.substrate {
+            Substrate::Air => (1.0, 1.0, 0.0),
+            Substrate::Dielectric(eta) if front_face => (1.0, eta, 0.0),
+            Substrate::Dielectric(eta) => (eta, 1.0, 0.0),
+            Substrate::Conductor { eta, k } => (1.0, by_wavelength(&eta, wavelength), by_wavelength(&k, wavelength)),
+        }
+    }
+
+    // Index of refraction on the far side over the one on the side light arrives from, if light
+    // can pass through.
+    fn transmission_eta(&self, front_face: bool) -> Option<f64> {
+        match self.substrate {
+            Substrate::Air => Some(1.0),
+            Substrate::Dielectric(eta) => Some(if front_face { eta } else { 1.0 / eta }),
+            Substrate::Conductor { .. } => None,
+        }
+    }
+}
+
+impl Material for ThinFilm {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+    ) -> Option<(Color, Ray)> {
+        let wo = -r_in.direction().unit();
+        let cos_theta = wo.dot(&hit_record.normal).min(1.0);
+        let (min, max) = self.thickness_range;
+        let amount = luminance(self.thickness.value(hit_record.u, hit_record.v, &hit_record.point)).clamp(0.0, 1.0);
+        let thickness = min + (max - min) * amount;
+        let reflectance = spectrum::from_fn(|wavelength| {
+            let (eta_i, eta_t, k_t) = self.media(hit_record.front_face, wavelength);
+            microfacet::fresnel_thin_film(cos_theta, eta_i, self.film_ior, eta_t, k_t, thickness, wavelength)
+        });
+        // Colours outside the RGB gamut come back slightly negative.
+        let clamp = |x: f64| x.clamp(0.0, 1.0);
+        let reflectance = Color::from(clamp(reflectance.x()), clamp(reflectance.y()), clamp(reflectance.z()));
+        let reflected = Ray::from_time(hit_record.point, microfacet::reflect(&wo, &hit_record.normal), r_in.time());
+
+        // The film is too thin to shift the light that passes through it, so that refracts as if
+        // it were not there.
+        let transmitted = self.transmission_eta(hit_record.front_face)
+            .and_then(|eta| microfacet::refract(&wo, &hit_record.normal, eta));
+        let Some(transmitted) = transmitted else {
+            return Some((reflectance, reflected));
+        };
+
+        // Choose between reflection and transmission by the mean reflectance of the channels.
+        let p = ((reflectance.x() + reflectance.y() + reflectance.z()) / 3.0).clamp(0.0, 1.0);
+        if random_double() < p {
+            Some((reflectance / p, reflected))
+        } else {
+            let transmittance = Color::from(1.0, 1.0, 1.0) - reflectance;
+            Some((transmittance / (1.0 - p), Ray::from_time(hit_record.point, transmitted, r_in.time())))
+        }
+    }
+
+    fn importance_scale(
+        &self,
+        _r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> f64 {
+        match self.transmission_eta(hit_record.front_face) {
+            Some(eta) if scattered.direction().dot(&hit_record.normal) < 0.0 => 1.0 / (eta * eta),
+            _ => 1.0,
+        }
+    }
+}
+
+// Linear interpolation of a per channel value between the channel wavelengths.
+fn by_wavelength(color: &Color, wavelength: f64) -> f64 {
+    let [red, green, blue] = CHANNEL_WAVELENGTHS;
+    if wavelength >= red {
+        color.x()
+    } else if wavelength >= green {
+        color.y() + (color.x() - color.y()) * (wavelength - green) / (red - green)
+    } else if wavelength >= blue {
+        color.z() + (color.y() - color.z()) * (wavelength - blue) / (green - blue)
+    } else {
+        color.z()
+    }
+}
+
+// === Diffuse Light (Emitter) ===
+
+pub struct DiffuseLight {
+    emit: Color,
+}
+
+impl DiffuseLight {
+    pub fn from(color: &Color) -> DiffuseLight {
+        DiffuseLight {
+            emit: *color,
+        }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(
+        &self,
+        _r_in: &Ray,
+        _hit_record: &HitRecord,
+    ) -> Option<(Color, Ray)> {
+        None
+    }
+
+    // Only the outward facing side emits.
+    fn emitted(
+        &self,
+        _r_in: &Ray,
+        hit_record: &HitRecord,
+    ) -> Color {
+        if hit_record.front_face {
+            spectrum::upsample(&self.emit)
+        } else {
+            Color::new()
+        }
+    }
+}
+
+// === Isotropic (Phase Function) ===
+
+pub struct Isotropic {
+    albedo: Color,
+}
+
+impl Isotropic {
+    pub fn from(color: &Color) -> Isotropic {
+        Isotropic {
+            albedo: *color,
+        }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+    ) -> Option<(Color, Ray)> {
+        Some((
+            spectrum::upsample(&self.albedo),
+            Ray::from_time(hit_record.point, Vec3::random_unit_vector(), r_in.time())
+        ))
+    }
+
+    fn eval(
+        &self,
+        _r_in: &Ray,
+        _hit_record: &HitRecord,
+        _scattered: &Ray,
+    ) -> Color {
+        spectrum::upsample(&self.albedo) / (4.0 * PI)
+    }
+
+    fn pdf(
+        &self,
+        _r_in: &Ray,
+        _hit_record: &HitRecord,
+        _scattered: &Ray,
+    ) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn is_volumetric(&self) -> bool {
+        true
+    }
+
+    fn is_specular(&self, _hit_record: &HitRecord) -> bool {
+        false
+    }
+}
+
+// === Henyey-Greenstein (Anisotropic Phase Function) ===
+
+// 'g' in (-1, 1) is the mean cosine of the scattering angle:
+// positive scatters forward (haze, clouds), negative backward, zero is isotropic.
+pub struct HenyeyGreenstein {
+    albedo: Color,
+    g: f64,
+}
+
+impl HenyeyGreenstein {
+    pub fn from(color: &Color, g: f64) -> HenyeyGreenstein {
+        HenyeyGreenstein {
+            albedo: *color,
+            g: g.clamp(-0.99, 0.99),
+        }
+    }
+
+    // Density of scattering by angle theta from the direction of travel.
+    fn phase(&self, cos_theta: f64) -> f64 {
+        let g = self.g;
+        let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+        (1.0 - g * g) / (4.0 * PI * denom * denom.sqrt())
+    }
+}
+
+impl Material for HenyeyGreenstein {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+    ) -> Option<(Color, Ray)> {
+        let g = self.g;
+        let xi = random_double();
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * xi
+        } else {
+            let sqr_term = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+            (1.0 + g * g - sqr_term * sqr_term) / (2.0 * g)
+        }.clamp(-1.0, 1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * random_double();
+
+        // About the direction of travel.
+        let basis = Onb::from_w(&r_in.direction());
+        let direction = basis.local(&Vec3::from(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta));
+
+        Some((
+            spectrum::upsample(&self.albedo),
+            Ray::from_time(hit_record.point, direction, r_in.time())
+        ))
+    }
+
+    fn eval(
+        &self,
+        r_in: &Ray,
+        _hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> Color {
+        spectrum::upsample(&self.albedo) * self.phase(r_in.direction().unit().dot(&scattered.direction().unit()))
+    }
+
+    fn pdf(
+        &self,
+        r_in: &Ray,
+        _hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> f64 {
+        self.phase(r_in.direction().unit().dot(&scattered.direction().unit()))
+    }
+
+    fn is_volumetric(&self) -> bool {
+        true
+    }
+
+    fn is_specular(&self, _hit_record: &HitRecord) -> bool {
+        false
+    }
 }
@@ -0,0 +1,331 @@
+use std::ops::{ Add, Sub, Mul, Div };
+
+use crate::common::*;
+
+// Microfacet theory: a rough surface is a mix of tiny mirrors whose normals follow a
+// distribution. Directions are in a shading frame with the surface normal along +z (see
+// 'Onb::to_local'), x and y along the two axes of anisotropic roughness.
+
+// === Trowbridge-Reitz (GGX) ===
+
+#[derive(Clone, Copy)]
+pub struct TrowbridgeReitz {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+#[allow(dead_code)]
+impl TrowbridgeReitz {
+    // Roughness in [0, 1] along x and y. It is perceptual: alpha = roughness^2.
+    pub fn from(roughness_x: f64, roughness_y: f64) -> TrowbridgeReitz {
+        TrowbridgeReitz {
+            alpha_x: TrowbridgeReitz::roughness_to_alpha(roughness_x),
+            alpha_y: TrowbridgeReitz::roughness_to_alpha(roughness_y),
+        }
+    }
+
+    pub fn from_alpha(alpha_x: f64, alpha_y: f64) -> TrowbridgeReitz {
+        TrowbridgeReitz { alpha_x, alpha_y }
+    }
+
+    pub fn roughness_to_alpha(roughness: f64) -> f64 {
+        let roughness = roughness.clamp(0.0, 1.0);
+        roughness * roughness
+    }
+
+    // So smooth that the surface is better treated as a perfect mirror (or window).
+    pub fn effectively_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < 1e-3
+    }
+
+    // Density of microfacet normals 'wm' per projected area.
+    pub fn d(&self, wm: &Vec3) -> f64 {
+        let cos2_theta = wm.z() * wm.z();
+        if cos2_theta == 0.0 {
+            return 0.0;
+        }
+        let tan2_theta = sin2_theta(wm) / cos2_theta;
+        if !tan2_theta.is_finite() {
+            return 0.0;
+        }
+        let e = tan2_theta * (cos2_phi(wm) / (self.alpha_x * self.alpha_x) + sin2_phi(wm) / (self.alpha_y * self.alpha_y));
+        1.0 / (PI * self.alpha_x * self.alpha_y * cos2_theta * cos2_theta * (1.0 + e) * (1.0 + e))
+    }
+
+    // Smith's auxiliary function, the masked microfacet area per visible area along 'w'.
+    fn lambda(&self, w: &Vec3) -> f64 {
+        let cos2_theta = w.z() * w.z();
+        if cos2_theta == 0.0 {
+            return INFINITY;
+        }
+        let tan2_theta = sin2_theta(w) / cos2_theta;
+        let alpha2 = cos2_phi(w) * self.alpha_x * self.alpha_x + sin2_phi(w) * self.alpha_y * self.alpha_y;
+        ((1.0 + alpha2 * tan2_theta).sqrt() - 1.0) / 2.0
+    }
+
+    // Fraction of the microfacets visible from 'w'.
+    pub fn g1(&self, w: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // Fraction of the microfacets visible from both 'wo' and 'wi' (height correlated).
+    pub fn g(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // Density of the normals 'wm' visible from 'w', which 'sample_wm' draws from.
+    pub fn pdf(&self, w: &Vec3, wm: &Vec3) -> f64 {
+        if w.z() == 0.0 {
+            return 0.0;
+        }
+        self.g1(w) / w.z().abs() * self.d(wm) * w.dot(wm).abs()
+    }
+
+    // A microfacet normal visible from 'w' (Heitz, sampling the visible normals).
+    pub fn sample_wm(&self, w: &Vec3) -> Vec3 {
+        // Stretch to the hemisphere configuration with unit roughness.
+        let mut wh = Vec3::from(self.alpha_x * w.x(), self.alpha_y * w.y(), w.z()).unit();
+        if wh.z() < 0.0 {
+            wh = -wh;
+        }
+        let t1 = if wh.z() < 0.99999 { Vec3::from(0.0, 0.0, 1.0).cross(&wh).unit() } else { Vec3::from(1.0, 0.0, 0.0) };
+        let t2 = wh.cross(&t1);
+
+        // A point on the projected disk, squeezed onto the visible half.
+        let r = random_double().sqrt();
+        let phi = 2.0 * PI * random_double();
+        let (px, py) = (r * phi.cos(), r * phi.sin());
+        let h = (1.0 - px * px).sqrt();
+        let s = (1.0 + wh.z()) / 2.0;
+        let py = (1.0 - s) * h + s * py;
+        let pz = (1.0 - px * px - py * py).max(0.0).sqrt();
+
+        let nh = px * t1 + py * t2 + pz * wh;
+        Vec3::from(self.alpha_x * nh.x(), self.alpha_y * nh.y(), nh.z().max(1e-6)).unit()
+    }
+}
+
+// === Charlie (Sheen) ===
+
+// Microfacet normals of fibres standing up from cloth, mostly tilted far from the surface
+// normal (Estevez and Kulla, "Production Friendly Microfacet Sheen BRDF").
+#[derive(Clone, Copy)]
+pub struct Charlie {
+    alpha: f64,
+}
+
+#[allow(dead_code)]
+impl Charlie {
+    // Roughness in (0, 1], perceptual like 'TrowbridgeReitz::from'. Smaller is a tighter rim.
+    pub fn from(roughness: f64) -> Charlie {
+        Charlie {
+            alpha: TrowbridgeReitz::roughness_to_alpha(roughness).max(1e-2),
+        }
+    }
+
+    // Density of microfacet normals 'wm' per projected area.
+    pub fn d(&self, wm: &Vec3) -> f64 {
+        if wm.z() <= 0.0 {
+            return 0.0;
+        }
+        let inv_alpha = 1.0 / self.alpha;
+        (2.0 + inv_alpha) * sin2_theta(wm).powf(0.5 * inv_alpha) / (2.0 * PI)
+    }
+
+    // Shadowing and masking together with the 1 / (4 cos_o cos_i) of the BRDF (Neubelt and
+    // Pettineo's simplification, as microfacet sheen is usually shaded).
+    pub fn visibility(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        1.0 / (4.0 * (wi.z() + wo.z() - wi.z() * wo.z()))
+    }
+
+    // Density of the normals 'sample_wm' draws, d(wm) * cos_m per solid angle.
+    pub fn pdf(&self, wm: &Vec3) -> f64 {
+        self.d(wm) * wm.z().max(0.0)
+    }
+
+    // Fraction of the light arriving along 'wo' (by its cosine) that the sheen lobe reflects.
+    // Numerical quadrature over the hemisphere, so slow enough to be worth tabulating.
+    pub fn albedo(&self, cos_theta_o: f64) -> f64 {
+        const STEPS: usize = 32;
+        let cos_theta_o = cos_theta_o.clamp(1e-3, 1.0);
+        let wo = Vec3::from((1.0 - cos_theta_o * cos_theta_o).sqrt(), 0.0, cos_theta_o);
+        let mut sum = 0.0;
+        for i in 0..STEPS {
+            let cos_theta_i = (i as f64 + 0.5) / STEPS as f64;
+            let sin_theta_i = (1.0 - cos_theta_i * cos_theta_i).sqrt();
+            for j in 0..2 * STEPS {
+                let phi = PI * (j as f64 + 0.5) / STEPS as f64;
+                let wi = Vec3::from(sin_theta_i * phi.cos(), sin_theta_i * phi.sin(), cos_theta_i);
+                let wm = (wo + wi).unit();
+                sum += self.d(&wm) * self.visibility(&wo, &wi) * cos_theta_i;
+            }
+        }
+        // Each sample covers d(cos_theta) d(phi) = (1 / STEPS) (PI / STEPS) of the hemisphere.
+        sum * PI / (STEPS * STEPS) as f64
+    }
+
+    // A microfacet normal weighted by its projected area, whose sin(theta) has the cumulative
+    // distribution sin(theta)^(2 + 1 / alpha).
+    pub fn sample_wm(&self) -> Vec3 {
+        let sin_theta = random_double().powf(1.0 / (2.0 + 1.0 / self.alpha));
+        let cos_theta = (1.0 - sin_theta * sin_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * random_double();
+        Vec3::from(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+    }
+}
+
+fn sin2_theta(w: &Vec3) -> f64 {
+    (1.0 - w.z() * w.z()).max(0.0)
+}
+
+fn cos2_phi(w: &Vec3) -> f64 {
+    let sin2 = sin2_theta(w);
+    if sin2 == 0.0 { 1.0 } else { (w.x() * w.x() / sin2).clamp(0.0, 1.0) }
+}
+
+fn sin2_phi(w: &Vec3) -> f64 {
+    let sin2 = sin2_theta(w);
+    if sin2 == 0.0 { 0.0 } else { (w.y() * w.y() / sin2).clamp(0.0, 1.0) }
+}
+
+// Mirror 'w' about 'n'. Both point away from the surface.
+pub fn reflect(w: &Vec3, n: &Vec3) -> Vec3 {
+    -*w + 2.0 * w.dot(n) * *n
+}
+
+// Refract 'w' through the interface with normal 'n' (on the side of 'w') into a medium with
+// relative index of refraction 'eta'. Both point away from the surface. None on total internal
+// reflection.
+pub fn refract(w: &Vec3, n: &Vec3, eta: f64) -> Option<Vec3> {
+    let cos_theta_i = w.dot(n);
+    let sin2_theta_t = (1.0 - cos_theta_i * cos_theta_i).max(0.0) / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        return None;
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+    Some(-*w / eta + (cos_theta_i / eta - cos_theta_t) * *n)
+}
+
+// === Fresnel ===
+
+// Unpolarized reflectance of light arriving at 'cos_theta_i' from the normal on the interface
+// to a medium with relative index of refraction 'eta'. Negative cosines arrive from inside.
+pub fn fresnel_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    let (cos_theta_i, eta) = if cos_theta_i < 0.0 { (-cos_theta_i, 1.0 / eta) } else { (cos_theta_i, eta) };
+    let cos_theta_i = cos_theta_i.min(1.0);
+
+    let sin2_theta_t = (1.0 - cos_theta_i * cos_theta_i) / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        return 1.0; // Total internal reflection
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+
+    let r_parallel = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let r_perpendicular = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    (r_parallel * r_parallel + r_perpendicular * r_perpendicular) / 2.0
+}
+
+// Reflectance per channel of a conductor with complex index of refraction eta + ik.
+pub fn fresnel_conductor(cos_theta_i: f64, eta: &Color, k: &Color) -> Color {
+    let f = |i: usize| fresnel_complex(cos_theta_i, Complex::from(eta[i], k[i]));
+    Color::from(f(0), f(1), f(2))
+}
+
+fn fresnel_complex(cos_theta_i: f64, eta: Complex) -> f64 {
+    let cos_theta_i = Complex::from(cos_theta_i.clamp(0.0, 1.0), 0.0);
+    let sin2_theta_i = Complex::from(1.0, 0.0) - cos_theta_i * cos_theta_i;
+    let sin2_theta_t = sin2_theta_i / (eta * eta);
+    let cos_theta_t = (Complex::from(1.0, 0.0) - sin2_theta_t).sqrt();
+
+    let r_parallel = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let r_perpendicular = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    (r_parallel.norm() + r_perpendicular.norm()) / 2.0
+}
+
+// Unpolarized reflectance of a film of index 'film_eta' and 'thickness' (nm) on a substrate of
+// complex index 'eta_t' + i 'k_t', for light of 'wavelength' (nm) arriving at 'cos_theta_i' from
+// a medium of index 'eta_i'. The reflections off the two sides of the film interfere (Airy).
+pub fn fresnel_thin_film(cos_theta_i: f64, eta_i: f64, film_eta: f64, eta_t: f64, k_t: f64, thickness: f64, wavelength: f64) -> f64 {
+    let one = Complex::from(1.0, 0.0);
+    let cos_theta_i = cos_theta_i.clamp(0.0, 1.0);
+    let sin2_theta_i = 1.0 - cos_theta_i * cos_theta_i;
+    let (n1, n2, n3) = (Complex::from(eta_i, 0.0), Complex::from(film_eta, 0.0), Complex::from(eta_t, k_t));
+    let cos_theta = |n: Complex| (one - Complex::from(eta_i * eta_i * sin2_theta_i, 0.0) / (n * n)).sqrt();
+    let (cos1, cos2, cos3) = (Complex::from(cos_theta_i, 0.0), cos_theta(n2), cos_theta(n3));
+
+    // Phase delay of each round trip through the film. It decays where the film reflects totally.
+    let phase = n2 * cos2 * Complex::from(4.0 * PI * thickness / wavelength, 0.0);
+    let decay = (-phase.im).exp();
+    let delay = Complex::from(decay * phase.re.cos(), decay * phase.re.sin());
+
+    let r_perpendicular = |na: Complex, ca: Complex, nb: Complex, cb: Complex| (na * ca - nb * cb) / (na * ca + nb * cb);
+    let r_parallel = |na: Complex, ca: Complex, nb: Complex, cb: Complex| (nb * ca - na * cb) / (nb * ca + na * cb);
+    let airy = |r12: Complex, r23: Complex| ((r12 + r23 * delay) / (one + r12 * r23 * delay)).norm();
+    let perpendicular = airy(r_perpendicular(n1, cos1, n2, cos2), r_perpendicular(n2, cos2, n3, cos3));
+    let parallel = airy(r_parallel(n1, cos1, n2, cos2), r_parallel(n2, cos2, n3, cos3));
+    ((perpendicular + parallel) / 2.0).min(1.0)
+}
+
+#[derive(Clone, Copy)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn from(re: f64, im: f64) -> Complex {
+        Complex { re, im }
+    }
+
+    // Squared magnitude.
+    fn norm(&self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    // Principal square root.
+    fn sqrt(self) -> Complex {
+        let n = self.norm().sqrt();
+        if n == 0.0 {
+            return Complex::from(0.0, 0.0);
+        }
+        let t1 = (0.5 * (n + self.re.abs())).sqrt();
+        let t2 = 0.5 * self.im / t1;
+        if self.re >= 0.0 {
+            Complex::from(t1, t2)
+        } else {
+            Complex::from(t2.abs(), t1.copysign(self.im))
+        }
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+    fn add(self, other: Complex) -> Complex {
+        Complex::from(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+    fn sub(self, other: Complex) -> Complex {
+        Complex::from(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+    fn mul(self, other: Complex) -> Complex {
+        Complex::from(self.re * other.re - self.im * other.im, self.re * other.im + self.im * other.re)
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+    fn div(self, other: Complex) -> Complex {
+        let scale = 1.0 / other.norm();
+        Complex::from(
+            scale * (self.re * other.re + self.im * other.im),
+            scale * (self.im * other.re - self.re * other.im),
+        )
+    }
+}
@@ -0,0 +1,225 @@
+use std::sync::Arc;
+
+use crate::common::*;
+use crate::hittable::HitRecord;
+use crate::material::Material;
+use crate::texture::{ self, Texture };
+
+// Materials that blend others, such as rust stains on metal or decals, without extra geometry.
+// Each scattering event picks one material with probability equal to its weight at the hit
+// point and returns its scattering, so the weight cancels out. 'eval' and 'pdf' are the weighted
+// sums over the materials, the density of that choice included.
+
+// === Mix ===
+
+// Two materials, 'b' where 'mask' is white and 'a' where it is black. Scalar masks read the
+// luminance of the texture.
+pub struct MixMaterial {
+    a: Arc<dyn Material + Send + Sync>,
+    b: Arc<dyn Material + Send + Sync>,
+    mask: Arc<dyn Texture + Send + Sync>,
+}
+
+#[allow(dead_code)]
+impl MixMaterial {
+    // 'amount' of 'b' everywhere, in [0, 1].
+    pub fn from(a: Arc<dyn Material + Send + Sync>, b: Arc<dyn Material + Send + Sync>, amount: f64) -> MixMaterial {
+        MixMaterial::masked(a, b, texture::constant(amount))
+    }
+
+    pub fn masked(a: Arc<dyn Material + Send + Sync>, b: Arc<dyn Material + Send + Sync>, mask: Arc<dyn Texture + Send + Sync>) -> MixMaterial {
+        MixMaterial { a, b, mask }
+    }
+
+    fn components(&self, hit_record: &HitRecord) -> [Component<'_>; 2] {
+        let amount = luminance(self.mask.value(hit_record.u, hit_record.v, &hit_record.point)).clamp(0.0, 1.0);
+        [(self.a.as_ref(), 1.0 - amount), (self.b.as_ref(), amount)]
+    }
+}
+
+impl Material for MixMaterial {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+    ) -> Option<(Color, Ray)> {
+        scatter(&self.components(hit_record), r_in, hit_record)
+    }
+
+    fn emitted(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+    ) -> Color {
+        emitted(&self.components(hit_record), r_in, hit_record)
+    }
+
+    fn eval(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> Color {
+        eval(&self.components(hit_record), r_in, hit_record, scattered)
+    }
+
+    fn pdf(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> f64 {
+        pdf(&self.components(hit_record), r_in, hit_record, scattered)
+    }
+
+    fn importance_scale(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> f64 {
+        importance_scale(&self.components(hit_record), r_in, hit_record, scattered)
+    }
+
+    fn is_specular(&self, hit_record: &HitRecord) -> bool {
+        is_specular(&self.components(hit_record), hit_record)
+    }
+}
+
+// === Blend ===
+
+// Any number of materials, each with a weight texture. Weights are normalized where they do
+// not sum to 1.
+pub struct Blend {
+    layers: Vec<(Arc<dyn Material + Send + Sync>, Arc<dyn Texture + Send + Sync>)>,
+}
+
+#[allow(dead_code)]
+impl Blend {
+    pub fn from(layers: Vec<(Arc<dyn Material + Send + Sync>, Arc<dyn Texture + Send + Sync>)>) -> Blend {
+        Blend { layers }
+    }
+
+    pub fn constant(layers: Vec<(Arc<dyn Material + Send + Sync>, f64)>) -> Blend {
+        Blend::from(layers.into_iter().map(|(material, weight)| (material, texture::constant(weight))).collect())
+    }
+
+    fn components(&self, hit_record: &HitRecord) -> Vec<Component<'_>> {
+        let (u, v, point) = (hit_record.u, hit_record.v, &hit_record.point);
+        let mut components: Vec<Component<'_>> = self.layers.iter()
+            .map(|(material, weight)| (material.as_ref(), luminance(weight.value(u, v, point)).max(0.0)))
+            .collect();
+        let total: f64 = components.iter().map(|(_, weight)| weight).sum();
+        if total > 0.0 {
+            components.iter_mut().for_each(|(_, weight)| *weight /= total);
+        }
+        components
+    }
+}
+
+impl Material for Blend {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+    ) -> Option<(Color, Ray)> {
+        scatter(&self.components(hit_record), r_in, hit_record)
+    }
+
+    fn emitted(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+    ) -> Color {
+        emitted(&self.components(hit_record), r_in, hit_record)
+    }
+
+    fn eval(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> Color {
+        eval(&self.components(hit_record), r_in, hit_record, scattered)
+    }
+
+    fn pdf(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> f64 {
+        pdf(&self.components(hit_record), r_in, hit_record, scattered)
+    }
+
+    fn importance_scale(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> f64 {
+        importance_scale(&self.components(hit_record), r_in, hit_record, scattered)
+    }
+
+    fn is_specular(&self, hit_record: &HitRecord) -> bool {
+        is_specular(&self.components(hit_record), hit_record)
+    }
+}
+
+// === Weighted Materials ===
+
+// A material and its weight at a hit point. The weights sum to 1, or are all 0.
+type Component<'a> = (&'a (dyn Material + Send + Sync), f64);
+
+fn scatter(components: &[Component<'_>], r_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+    let mut xi = random_double();
+    let (material, _) = components.iter()
+        .filter(|(_, weight)| *weight > 0.0)
+        .find(|(_, weight)| {
+            xi -= weight;
+            xi < 0.0
+        })
+        .or_else(|| components.iter().rev().find(|(_, weight)| *weight > 0.0))?;
+    material.scatter(r_in, hit_record)
+}
+
+fn emitted(components: &[Component<'_>], r_in: &Ray, hit_record: &HitRecord) -> Color {
+    components.iter().fold(Color::new(), |sum, (material, weight)| sum + material.emitted(r_in, hit_record) * *weight)
+}
+
+fn eval(components: &[Component<'_>], r_in: &Ray, hit_record: &HitRecord, scattered: &Ray) -> Color {
+    components.iter()
+        .filter(|(_, weight)| *weight > 0.0)
+        .fold(Color::new(), |sum, (material, weight)| sum + material.eval(r_in, hit_record, scattered) * *weight)
+}
+
+fn pdf(components: &[Component<'_>], r_in: &Ray, hit_record: &HitRecord, scattered: &Ray) -> f64 {
+    components.iter()
+        .filter(|(_, weight)| *weight > 0.0)
+        .map(|(material, weight)| weight * material.pdf(r_in, hit_record, scattered))
+        .sum()
+}
+
+// The scale of each material weighted by how much it scatters along 'scattered'. Specular
+// materials cannot say, but only refraction rescales, so the materials that rescale 'scattered'
+// are the ones that can have refracted it.
+fn importance_scale(components: &[Component<'_>], r_in: &Ray, hit_record: &HitRecord, scattered: &Ray) -> f64 {
+    let specular = is_specular(components, hit_record);
+    let (mut sum, mut total) = (0.0, 0.0);
+    for (material, weight) in components.iter().filter(|(_, weight)| *weight > 0.0) {
+        let scale = material.importance_scale(r_in, hit_record, scattered);
+        let share = match specular {
+            true if scale == 1.0 => continue,
+            true => *weight,
+            false => weight * luminance(material.eval(r_in, hit_record, scattered)),
+        };
+        sum += share * scale;
+        total += share;
+    }
+    if total > 0.0 { sum / total } else { 1.0 }
+}
+
+// Any specular material with weight makes the mix specular, as its part can only be sampled.
+fn is_specular(components: &[Component<'_>], hit_record: &HitRecord) -> bool {
+    components.iter().any(|(material, weight)| *weight > 0.0 && material.is_specular(hit_record))
+}
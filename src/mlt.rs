@@ -0,0 +1,264 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+use indicatif::ProgressBar;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::common::*;
+use crate::camera::Camera;
+use crate::film::Film;
+use crate::hittable_list::HittableList;
+use crate::sampler::{ self, Sampler };
+use crate::spectrum;
+
+// Primary sample space Metropolis light transport (Kelemen et al. 2002).
+// A path is a deterministic function of the numbers returned by 'random_double': the first
+// two pick the raster position, the rest drive 'get_ray_raster' and every 'scatter' along
+// the path. Markov chains mutate that vector, either slightly (small steps, for exploring
+// a hard to find light path) or completely (large steps, for ergodicity), and accept with
+// probability min(1, f(proposed) / f(current)) where f is the luminance of the path (its
+// magnitude, as spectral samples out of the RGB gamut can be negative).
+//
+// The chains only decide where contributions go; the overall brightness comes from b,
+// the mean of f estimated by independent bootstrap paths, which is unbiased.
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct MetropolisInfo {
+    pub bootstrap_samples: usize,    // Independent paths used to estimate b and seed the chains
+    pub chains: usize,               // Independent Markov chains, run in parallel
+    pub large_step_probability: f64,
+    pub sigma: f64,                  // Standard deviation of small step mutations
+}
+
+impl Default for MetropolisInfo {
+    fn default() -> Self {
+        MetropolisInfo {
+            bootstrap_samples: 100_000,
+            chains: 1000,
+            large_step_probability: 0.3,
+            sigma: 0.01,
+        }
+    }
+}
+
+// === Primary Sample Space ===
+
+#[derive(Clone, Copy)]
+struct PrimarySample {
+    value: f64,
+    last_modification: u64,
+    value_backup: f64,
+    modify_backup: u64,
+}
+
+// Mutations are applied lazily: a sample is only brought up to date with the current
+// iteration when the path actually asks for it.
+struct MltSampler {
+    rng: StdRng,
+    sigma: f64,
+    large_step_probability: f64,
+    samples: Vec<PrimarySample>,
+    current_iteration: u64,
+    large_step: bool,
+    last_large_step_iteration: u64,
+    sample_index: usize,
+}
+
+impl MltSampler {
+    fn new(seed: u64, sigma: f64, large_step_probability: f64) -> MltSampler {
+        MltSampler {
+            rng: StdRng::seed_from_u64(seed),
+            sigma,
+            large_step_probability,
+            samples: Vec::new(),
+            current_iteration: 0,
+            large_step: true,
+            last_large_step_iteration: 0,
+            sample_index: 0,
+        }
+    }
+
+    fn start_iteration(&mut self) {
+        self.current_iteration += 1;
+        self.large_step = self.rng.gen_range(0.0..1.0) < self.large_step_probability;
+        self.sample_index = 0;
+    }
+
+    fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step_iteration = self.current_iteration;
+        }
+    }
+
+    fn reject(&mut self) {
+        for sample in self.samples.iter_mut() {
+            if sample.last_modification == self.current_iteration {
+                sample.value = sample.value_backup;
+                sample.last_modification = sample.modify_backup;
+            }
+        }
+        self.current_iteration -= 1;
+    }
+
+    fn ensure_ready(&mut self, index: usize) {
+        // Coordinates the chain has never used are uniformly distributed. Paths consume a
+        // varying number of them, e.g. in the rejection loops of 'Vec3::random_in_unit_sphere'.
+        if index >= self.samples.len() {
+            let value = self.rng.gen_range(0.0..1.0);
+            self.samples.push(PrimarySample {
+                value,
+                last_modification: self.current_iteration,
+                value_backup: value,
+                modify_backup: self.current_iteration,
+            });
+            return;
+        }
+        let sample = &mut self.samples[index];
+
+        // Catch up with the last large step, which this sample missed.
+        if sample.last_modification < self.last_large_step_iteration {
+            sample.value = self.rng.gen_range(0.0..1.0);
+            sample.last_modification = self.last_large_step_iteration;
+        }
+
+        sample.value_backup = sample.value;
+        sample.modify_backup = sample.last_modification;
+
+        if self.large_step {
+            sample.value = self.rng.gen_range(0.0..1.0);
+        } else {
+            // n small steps in a row add up to one with sigma * sqrt(n).
+            let small_steps = self.current_iteration.saturating_sub(sample.last_modification) as f64;
+            let sigma = self.sigma * small_steps.sqrt();
+            sample.value += sigma * standard_normal(&mut self.rng);
+            sample.value -= sample.value.floor();
+        }
+        sample.last_modification = self.current_iteration;
+    }
+}
+
+impl Sampler for MltSampler {
+    fn next(&mut self) -> f64 {
+        let index = self.sample_index;
+        self.sample_index += 1;
+        self.ensure_ready(index);
+        self.samples[index].value
+    }
+}
+
+// Box-Muller transform.
+fn standard_normal(rng: &mut StdRng) -> f64 {
+    let u1: f64 = 1.0 - rng.gen_range(0.0..1.0);
+    let u2: f64 = rng.gen_range(0.0..1.0);
+    (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
+
+// === Render ===
+
+pub fn render(
+    camera: &Camera,
+    world: Arc<HittableList>,
+    film: &Film,
+    progress: Arc<Mutex<ProgressBar>>,
+    info: &MetropolisInfo,
+) {
+    let base_seed: u64 = rand::thread_rng().gen();
+
+    // Bootstrap: b is the mean of f over independent paths, and each chain starts from a
+    // bootstrap path chosen proportionally to f, so it starts in its stationary distribution.
+    let weights: Vec<f64> = (0..info.bootstrap_samples).into_par_iter().map(|index| {
+        let sampler = Rc::new(RefCell::new(MltSampler::new(base_seed.wrapping_add(index as u64), info.sigma, info.large_step_probability)));
+        let (_, _, color) = path_sample(camera, &world, sampler);
+        luminance(color).abs()
+    }).collect();
+    let b = weights.iter().sum::<f64>() / info.bootstrap_samples as f64;
+
+    if b > 0.0 {
+        let mut cdf = Vec::with_capacity(weights.len());
+        let mut sum = 0.0;
+        for weight in weights.iter() {
+            sum += weight;
+            cdf.push(sum);
+        }
+
+        // Every pixel receives 'samples_per_pixel' mutations on average.
+        let total_mutations = camera.samples_per_pixel() as u64 * (camera.image_width() * camera.image_height()) as u64;
+        let chains = info.chains as u64;
+
+        progress.lock().unwrap().set_length(chains);
+        (0..chains).into_par_iter().for_each(|chain| {
+            let mutations = total_mutations / chains + if chain < total_mutations % chains { 1 } else { 0 };
+            let u = rand::thread_rng().gen_range(0.0..sum);
+            let index = cdf.partition_point(|&c| c <= u).min(cdf.len() - 1);
+            run_chain(camera, &world, film, info, base_seed.wrapping_add(index as u64), mutations, b);
+            progress.lock().unwrap().inc(1);
+        });
+    }
+
+    progress.lock().unwrap().finish();
+}
+
+fn run_chain(camera: &Camera, world: &Arc<HittableList>, film: &Film, info: &MetropolisInfo, seed: u64, mutations: u64, b: f64) {
+    // Replaying the bootstrap seed reproduces the chosen bootstrap path.
+    let sampler = Rc::new(RefCell::new(MltSampler::new(seed, info.sigma, info.large_step_probability)));
+    let (mut x_current, mut y_current, mut color_current) = path_sample(camera, world, sampler.clone());
+    let mut f_current = luminance(color_current).abs();
+
+    for _ in 0..mutations {
+        sampler.borrow_mut().start_iteration();
+        let (x_proposed, y_proposed, color_proposed) = path_sample(camera, world, sampler.clone());
+        let f_proposed = luminance(color_proposed).abs();
+
+        let accept = if f_current > 0.0 { (f_proposed / f_current).min(1.0) } else { 1.0 };
+
+        // Expected values: both states are splatted, weighted by their acceptance.
+        if accept > 0.0 {
+            splat(film, x_proposed, y_proposed, color_proposed * (accept * b / f_proposed));
+        }
+        if f_current > 0.0 {
+            splat(film, x_current, y_current, color_current * ((1.0 - accept) * b / f_current));
+        }
+
+        if random_double() < accept {
+            x_current = x_proposed;
+            y_current = y_proposed;
+            color_current = color_proposed;
+            f_current = f_proposed;
+            sampler.borrow_mut().accept();
+        } else {
+            sampler.borrow_mut().reject();
+        }
+    }
+}
+
+// Trace one path with every random number drawn from 'sampler'.
+// Returns the raster position and the radiance carried by the path.
+fn path_sample(camera: &Camera, world: &Arc<HittableList>, sampler: Rc<RefCell<MltSampler>>) -> (f64, f64, Color) {
+    sampler::install(sampler);
+
+    let x = random_double() * camera.image_width() as f64;
+    let y = random_double() * camera.image_height() as f64;
+    spectrum::begin_sample(camera.spectral());
+    let color = match camera.get_ray_raster(x, y) {
+        Some(ray) => spectrum::to_rgb(&camera.ray_color(&ray, camera.max_depth(), world)),
+        None => Color::new(),
+    };
+
+    sampler::uninstall();
+
+    // Guard against NaNs and infinities, which would poison the chain.
+    if color.x().is_finite() && color.y().is_finite() && color.z().is_finite() {
+        (x, y, color)
+    } else {
+        (x, y, Color::new())
+    }
+}
+
+fn splat(film: &Film, x: f64, y: f64, color: Color) {
+    let i = (x as u32).min(film.width() - 1);
+    let j = (y as u32).min(film.height() - 1);
+    film.add_splat(i, j, color);
+}
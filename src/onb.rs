@@ -0,0 +1,62 @@
+use crate::mat3::Mat3;
+use crate::vec3::{ Vec3, Point3 };
+
+// Right handed orthonormal basis u, v, w (u x v = w).
+#[derive(Clone, Copy)]
+pub struct Onb {
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+#[allow(dead_code)]
+impl Onb {
+    // Any basis whose w is along 'w', e.g. for directions sampled about a normal.
+    pub fn from_w(w: &Vec3) -> Onb {
+        let w = w.unit();
+        let a = if w.x().abs() > 0.9 { Vec3::from(0.0, 1.0, 0.0) } else { Vec3::from(1.0, 0.0, 0.0) };
+        let v = w.cross(&a).unit();
+        let u = v.cross(&w);
+        Onb { u, v, w }
+    }
+
+    // The basis with w along 'w' and u as close to 'u' as possible, e.g. to orient anisotropic
+    // surfaces. Any basis about 'w' if 'u' is parallel to it.
+    pub fn from_wu(w: &Vec3, u: &Vec3) -> Onb {
+        let w = w.unit();
+        let u = *u - u.dot(&w) * w;
+        if u.abs2() < 1e-12 {
+            return Onb::from_w(&w);
+        }
+        let u = u.unit();
+        let v = w.cross(&u);
+        Onb { u, v, w }
+    }
+
+    // The basis of a viewer at 'from' looking at 'to': u points right, v up and w backward.
+    pub fn look_at(from: &Point3, to: &Point3, up: &Vec3) -> Onb {
+        let w = (*from - *to).unit();
+        let u = up.cross(&w).unit();
+        let v = w.cross(&u);
+        Onb { u, v, w }
+    }
+
+    pub fn u(&self) -> Vec3 { self.u }
+    pub fn v(&self) -> Vec3 { self.v }
+    pub fn w(&self) -> Vec3 { self.w }
+
+    // From coordinates in this basis to world coordinates.
+    pub fn local(&self, a: &Vec3) -> Vec3 {
+        a.x() * self.u + a.y() * self.v + a.z() * self.w
+    }
+
+    // From world coordinates to coordinates in this basis.
+    pub fn to_local(self, a: &Vec3) -> Vec3 {
+        Vec3::from(a.dot(&self.u), a.dot(&self.v), a.dot(&self.w))
+    }
+
+    // The rotation taking x, y, z to u, v, w.
+    pub fn to_mat3(self) -> Mat3 {
+        Mat3::from_columns(&self.u, &self.v, &self.w)
+    }
+}
@@ -0,0 +1,301 @@
+use std::sync::{Arc, Mutex};
+
+use indicatif::ProgressBar;
+use rayon::iter::{IntoParallelIterator, IndexedParallelIterator, ParallelIterator};
+use rayon::slice::ParallelSliceMut;
+
+use crate::common::*;
+use crate::camera::Camera;
+use crate::film::Film;
+use crate::hittable::HitRecord;
+use crate::hittable_list::HittableList;
+use crate::material;
+use crate::spectrum;
+
+// Photon mapping (Jensen), optionally progressive (Hachisuka & Jensen, stochastic PPM).
+// Each pass traces one camera ray per pixel through specular surfaces to a visible point,
+// emits photons from the lights, and estimates the radiance at the visible point from the
+// density of photons within a radius. In progressive mode every pixel shrinks its radius
+// as photons accumulate, so the blur of the density estimate vanishes over the passes.
+
+#[derive(Clone, Copy)]
+pub struct Photon {
+    pub point: Point3,
+    pub direction: Vec3, // Direction of travel
+    pub power: Color,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct PhotonMappingInfo {
+    pub photons_per_pass: usize, // Photons emitted from the lights in each pass
+    pub radius: f64,             // Initial gather radius
+    pub progressive: bool,       // Shrink the radius after each pass, making the estimate consistent
+}
+
+impl Default for PhotonMappingInfo {
+    fn default() -> Self {
+        PhotonMappingInfo {
+            photons_per_pass: 200_000,
+            radius: 0.1,
+            progressive: true,
+        }
+    }
+}
+
+// === Photon Map ===
+
+// Balanced kd-tree stored implicitly: the median of each range is its node.
+pub struct PhotonMap {
+    photons: Vec<Photon>,
+    axes: Vec<usize>,
+}
+
+#[allow(dead_code)]
+impl PhotonMap {
+    pub fn build(mut photons: Vec<Photon>) -> PhotonMap {
+        let mut axes = vec![0; photons.len()];
+        PhotonMap::build_range(&mut photons, &mut axes);
+        PhotonMap { photons, axes }
+    }
+
+    pub fn len(&self) -> usize {
+        self.photons.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.photons.is_empty()
+    }
+
+    // Call 'f' for every photon within 'radius' of 'point'.
+    pub fn for_each_in_radius<F: FnMut(&Photon)>(&self, point: &Point3, radius: f64, mut f: F) {
+        self.search(0, self.photons.len(), point, radius * radius, &mut f);
+    }
+
+    fn build_range(photons: &mut [Photon], axes: &mut [usize]) {
+        if photons.len() <= 1 {
+            return;
+        }
+
+        // Split along the axis of largest extent.
+        let mut min = [INFINITY; 3];
+        let mut max = [-INFINITY; 3];
+        for photon in photons.iter() {
+            for a in 0..3 {
+                min[a] = min[a].min(photon.point[a]);
+                max[a] = max[a].max(photon.point[a]);
+            }
+        }
+        let axis = (0..3).max_by(|&a, &b| (max[a] - min[a]).total_cmp(&(max[b] - min[b]))).unwrap();
+
+        let mid = photons.len() / 2;
+        photons.select_nth_unstable_by(mid, |p, q| p.point[axis].total_cmp(&q.point[axis]));
+        axes[mid] = axis;
+
+        let (left, right) = photons.split_at_mut(mid);
+        let (left_axes, right_axes) = axes.split_at_mut(mid);
+        PhotonMap::build_range(left, left_axes);
+        PhotonMap::build_range(&mut right[1..], &mut right_axes[1..]);
+    }
+
+    fn search<F: FnMut(&Photon)>(&self, begin: usize, end: usize, point: &Point3, radius2: f64, f: &mut F) {
+        if begin >= end {
+            return;
+        }
+        let mid = begin + (end - begin) / 2;
+        let photon = &self.photons[mid];
+        if (photon.point - *point).abs2() <= radius2 {
+            f(photon);
+        }
+        if end - begin == 1 {
+            return;
+        }
+
+        let axis = self.axes[mid];
+        let delta = point[axis] - photon.point[axis];
+        let (near, far) = if delta < 0.0 { ((begin, mid), (mid + 1, end)) } else { ((mid + 1, end), (begin, mid)) };
+        self.search(near.0, near.1, point, radius2, f);
+        if delta * delta <= radius2 {
+            self.search(far.0, far.1, point, radius2, f);
+        }
+    }
+}
+
+// === Render ===
+
+// Per pixel statistics of progressive photon mapping.
+struct PixelState {
+    radius: f64,
+    photon_count: f64, // N, the (discounted) number of photons gathered so far
+    flux: Color,       // tau, the (discounted) gathered flux
+    direct: Color,     // Emission seen directly, summed over passes
+}
+
+// A non-specular point seen from the camera, where photons are gathered.
+struct VisiblePoint {
+    record: HitRecord,
+    r_in: Ray,
+    beta: Color,
+}
+
+pub fn render(
+    camera: &Camera,
+    world: Arc<HittableList>,
+    lights: Arc<HittableList>,
+    film: &Film,
+    progress: Arc<Mutex<ProgressBar>>,
+    info: &PhotonMappingInfo,
+) {
+    let width = camera.image_width();
+    let passes = camera.samples_per_pixel();
+    let photons_per_pass = info.photons_per_pass;
+    let alpha = if info.progressive { 2.0 / 3.0 } else { 1.0 };
+
+    let mut states: Vec<PixelState> = (0..width * camera.image_height())
+        .map(|_| PixelState { radius: info.radius, photon_count: 0.0, flux: Color::new(), direct: Color::new() })
+        .collect();
+
+    progress.lock().unwrap().set_length(passes as u64);
+    for _ in 0..passes {
+        let photon_map = PhotonMap::build(trace_photons(camera, &world, &lights, photons_per_pass));
+
+        states.par_chunks_mut(width as usize).enumerate().for_each(|(j, row)| {
+            for (i, state) in row.iter_mut().enumerate() {
+                spectrum::begin_sample(false);
+                let Some(ray) = camera.get_ray(i as u32, j as u32) else {
+                    continue;
+                };
+                // Paths reduced to a single channel are scaled back up (see 'spectrum::single_channel').
+                let (direct, visible) = trace_camera(camera, &world, ray);
+                state.direct += spectrum::to_rgb(&direct);
+
+                let Some(visible) = visible else {
+                    continue;
+                };
+                let visible = VisiblePoint { beta: spectrum::to_rgb(&visible.beta), ..visible };
+                let (count, flux) = gather(&photon_map, &visible, state.radius);
+                if count == 0 {
+                    continue;
+                }
+
+                let photon_count = state.photon_count + alpha * count as f64;
+                let radius = state.radius * (photon_count / (state.photon_count + count as f64)).sqrt();
+                state.flux = (state.flux + flux) * (radius * radius / (state.radius * state.radius));
+                state.photon_count = photon_count;
+                state.radius = radius;
+            }
+        });
+
+        progress.lock().unwrap().inc(1);
+    }
+
+    // The film averages over the passes.
+    let emitted = passes as f64 * photons_per_pass as f64;
+    for (index, state) in states.iter().enumerate() {
+        let color = state.direct + state.flux * (passes as f64 / (emitted * PI * state.radius * state.radius));
+        film.add_sample(index as u32 % width, index as u32 / width, color);
+    }
+
+    progress.lock().unwrap().finish();
+}
+
+// Follow 'ray' through specular surfaces and media. Returns the emission picked up on the way
+// and the first non-specular surface point, if any.
+fn trace_camera(camera: &Camera, world: &HittableList, ray: Ray) -> (Color, Option<VisiblePoint>) {
+    let mut ray = ray;
+    let mut beta = Color::from(1.0, 1.0, 1.0);
+    let mut direct = Color::new();
+
+    for _ in 0..camera.max_depth() {
+        let Some(record) = world.hit(&ray, &Interval::from(0.001, INFINITY)) else {
+            direct += beta * camera.background(&ray);
+            return (direct, None);
+        };
+        beta = beta * material::arrival_transmittance(&ray, &record);
+        direct += beta * record.material.emitted(&ray, &record);
+
+        if !record.material.is_specular(&record) && !record.material.is_volumetric() {
+            return (direct, Some(VisiblePoint { record, r_in: ray, beta }));
+        }
+        let Some((attenuation, scattered)) = record.material.scatter(&ray, &record) else {
+            return (direct, None);
+        };
+        beta = beta * attenuation;
+        ray = material::scattered_in_medium(&ray, &record, scattered);
+    }
+
+    (direct, None)
+}
+
+// Photons gathered within 'radius' of a visible point, and their flux weighted by the BSDF.
+fn gather(photon_map: &PhotonMap, visible: &VisiblePoint, radius: f64) -> (usize, Color) {
+    let record = &visible.record;
+    let mut count = 0;
+    let mut flux = Color::new();
+
+    photon_map.for_each_in_radius(&record.point, radius, |photon| {
+        // Photons that landed on the other side of the surface only count if the material
+        // transmits, which 'eval' accounts for.
+        let to_light = Ray::from(record.point, -photon.direction);
+        let cosine = record.normal.dot(&to_light.direction().unit()).abs();
+        if cosine < EPS {
+            return;
+        }
+        count += 1;
+        let f = record.material.eval(&visible.r_in, record, &to_light) / cosine;
+        flux += visible.beta * f * photon.power;
+    });
+
+    (count, flux)
+}
+
+// Emit photons from 'lights' and store one at every non-specular surface they land on.
+// Media scatter photons but do not store them.
+// Photon powers are not divided by the photon count.
+// Each photon is emitted at a random instant while the shutter is open.
+fn trace_photons(camera: &Camera, world: &HittableList, lights: &HittableList, count: usize) -> Vec<Photon> {
+    (0..count).into_par_iter().flat_map_iter(|_| {
+        spectrum::begin_sample(false); // Photons from differently sampled wavelengths could not be summed
+        let mut photons = Vec::new();
+        let time = camera.sample_time();
+        let Some((record, pdf_pos)) = lights.sample_surface(time) else {
+            return photons.into_iter();
+        };
+
+        // Emit in a cosine weighted direction about the outward normal.
+        let mut direction = record.normal + Vec3::random_unit_vector();
+        if direction.is_zero() {
+            direction = record.normal;
+        }
+        let emit = record.material.emitted(&Ray::from(record.point + direction, -direction), &record);
+        if emit.is_zero() || pdf_pos == 0.0 {
+            return photons.into_iter();
+        }
+
+        // Le * cos / (pdf_pos * cos / pi)
+        let mut power = emit * (PI / pdf_pos);
+        let mut ray = Ray::from_time(record.point, direction, time);
+
+        for _ in 0..camera.max_depth() {
+            let Some(record) = world.hit(&ray, &Interval::from(0.001, INFINITY)) else {
+                break;
+            };
+            power = power * material::arrival_transmittance(&ray, &record);
+            if !record.material.is_specular(&record) && !record.material.is_volumetric() {
+                photons.push(Photon {
+                    point: record.point,
+                    direction: ray.direction().unit(),
+                    power: spectrum::to_rgb(&power),
+                });
+            }
+            let Some((attenuation, scattered)) = record.material.scatter(&ray, &record) else {
+                break;
+            };
+            power = power * attenuation * record.material.importance_scale(&ray, &record, &scattered);
+            if power.is_zero() {
+                break;
+            }
+            ray = material::scattered_in_medium(&ray, &record, scattered);
+        }
+
+        photons.into_iter()
+    }).collect()
+}
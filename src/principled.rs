@@ -0,0 +1,297 @@
+use std::sync::Arc;
+
+use crate::common::*;
+use crate::hittable::HitRecord;
+use crate::material::{ Material, RoughDielectric };
+use crate::microfacet::{ self, TrowbridgeReitz };
+use crate::spectrum;
+use crate::texture::{ self, Texture };
+
+// The Disney principled BSDF (Burley 2012, with the glass of Burley 2015): a handful of
+// artist friendly parameters in [0, 1] blend a diffuse lobe with sheen, a GGX specular lobe,
+// a clearcoat and rough glass. Every parameter is a texture; the scalar ones read its
+// luminance. Directions are sampled from one lobe, chosen in proportion to an estimate of
+// its reflectance, and weighted by the density of the whole mixture.
+
+// Sheen is tinted halfway towards the base colour.
+const SHEEN_TINT: f64 = 0.5;
+
+#[derive(Clone)]
+pub struct PrincipledInfo {
+    pub base_color: Arc<dyn Texture + Send + Sync>,
+    pub metallic: Arc<dyn Texture + Send + Sync>,
+    pub roughness: Arc<dyn Texture + Send + Sync>,
+    pub specular: Arc<dyn Texture + Send + Sync>,        // Dielectric reflectance, 0.5 for 4% at normal incidence
+    pub specular_tint: Arc<dyn Texture + Send + Sync>,   // Tints dielectric reflections towards the base colour
+    pub sheen: Arc<dyn Texture + Send + Sync>,           // Soft grazing highlight of cloth
+    pub clearcoat: Arc<dyn Texture + Send + Sync>,       // A second, white specular layer
+    pub clearcoat_gloss: Arc<dyn Texture + Send + Sync>, // 0 for satin, 1 for gloss
+    pub transmission: Arc<dyn Texture + Send + Sync>,    // How much of the dielectric is glass
+    pub anisotropic: Arc<dyn Texture + Send + Sync>,     // Stretches highlights along 'tangent'
+    pub subsurface: Arc<dyn Texture + Send + Sync>,      // Flattens the diffuse lobe like scattering under the surface
+    pub tangent: Vec3, // World direction, projected onto the surface
+}
+
+impl Default for PrincipledInfo {
+    fn default() -> Self {
+        PrincipledInfo {
+            base_color: texture::solid(&Color::from(0.8, 0.8, 0.8)),
+            metallic: texture::constant(0.0),
+            roughness: texture::constant(0.5),
+            specular: texture::constant(0.5),
+            specular_tint: texture::constant(0.0),
+            sheen: texture::constant(0.0),
+            clearcoat: texture::constant(0.0),
+            clearcoat_gloss: texture::constant(1.0),
+            transmission: texture::constant(0.0),
+            anisotropic: texture::constant(0.0),
+            subsurface: texture::constant(0.0),
+            tangent: Vec3::from(0.0, 1.0, 0.0),
+        }
+    }
+}
+
+pub struct Principled {
+    info: PrincipledInfo,
+}
+
+// The parameters at a hit point, and the lobes they make up.
+struct Lobes {
+    frame: Onb, // Normal on the side the ray arrives from, x along the tangent
+    base_color: Color,
+    roughness: f64,
+    subsurface: f64,
+    sheen: Color,
+    specular0: Color, // Specular reflectance at normal incidence
+    distribution: TrowbridgeReitz,
+    clearcoat: f64,
+    clearcoat_alpha: f64,
+    glass: RoughDielectric,
+    diffuse_weight: f64,
+    specular_weight: f64,
+    glass_weight: f64,
+    probabilities: [f64; 4], // Of sampling the diffuse, specular, clearcoat and glass lobes
+}
+
+#[allow(dead_code)]
+impl Principled {
+    pub fn from(info: PrincipledInfo) -> Principled {
+        Principled { info }
+    }
+
+    fn lobes(&self, r_in: &Ray, hit_record: &HitRecord) -> Lobes {
+        let info = &self.info;
+        let (u, v, point) = (hit_record.u, hit_record.v, &hit_record.point);
+        let scalar = |texture: &Arc<dyn Texture + Send + Sync>| luminance(texture.value(u, v, point)).clamp(0.0, 1.0);
+
+        let base_color = info.base_color.value(u, v, point);
+        let metallic = scalar(&info.metallic);
+        let roughness = scalar(&info.roughness);
+        let specular = scalar(&info.specular);
+        let transmission = scalar(&info.transmission);
+        let clearcoat = scalar(&info.clearcoat);
+
+        // Bidirectional methods evaluate the reverse direction with the same hit record, so
+        // the frame follows the side of 'r_in' rather than the record (see 'RoughDielectric').
+        let normal = if r_in.direction().dot(&hit_record.normal) > 0.0 { -hit_record.normal } else { hit_record.normal };
+        let frame = Onb::from_wu(&normal, &info.tangent);
+
+        let white = Color::from(1.0, 1.0, 1.0);
+        let tint = if luminance(base_color) > 0.0 { base_color / luminance(base_color) } else { white };
+        let specular0 = mix(0.08 * specular * mix(white, tint, scalar(&info.specular_tint)), base_color, metallic);
+        let sheen = scalar(&info.sheen) * mix(white, tint, SHEEN_TINT);
+
+        let aspect = (1.0 - 0.9 * scalar(&info.anisotropic)).sqrt();
+        let alpha = roughness * roughness;
+        let distribution = TrowbridgeReitz::from_alpha((alpha / aspect).max(0.001), (alpha * aspect).max(0.001));
+
+        // The glass has the index of refraction that reflects as much as 'specular' at normal
+        // incidence, and is kept just rough enough not to become a mirror, which could only be sampled.
+        let f0 = (0.08 * specular).sqrt();
+        let eta = ((1.0 + f0) / (1.0 - f0)).max(1.01);
+        let glass = RoughDielectric::from(eta, roughness.max(0.032));
+
+        let diffuse_weight = (1.0 - metallic) * (1.0 - transmission);
+        let specular_weight = 1.0 - (1.0 - metallic) * transmission;
+        let glass_weight = (1.0 - metallic) * transmission;
+
+        // Estimates of the reflectance of each lobe towards 'wo'.
+        let fresnel = schlick_weight(frame.to_local(&-r_in.direction().unit()).z());
+        let estimates = [
+            diffuse_weight * (luminance(base_color) + luminance(sheen)),
+            specular_weight * luminance(mix(specular0, white, fresnel)),
+            0.25 * clearcoat * lerp(0.04, 1.0, fresnel),
+            glass_weight,
+        ];
+        let total: f64 = estimates.iter().sum();
+        let probabilities = if total > 0.0 { estimates.map(|e| e / total) } else { [1.0, 0.0, 0.0, 0.0] };
+
+        Lobes {
+            frame,
+            base_color,
+            roughness,
+            subsurface: scalar(&info.subsurface),
+            sheen,
+            specular0,
+            distribution,
+            clearcoat,
+            clearcoat_alpha: lerp(0.1, 0.001, scalar(&info.clearcoat_gloss)),
+            glass,
+            diffuse_weight,
+            specular_weight,
+            glass_weight,
+            probabilities,
+        }
+    }
+
+    // BSDF times |cos| and the density of the lobe mixture.
+    fn evaluate(&self, lobes: &Lobes, r_in: &Ray, hit_record: &HitRecord, scattered: &Ray) -> (Color, f64) {
+        let wo = lobes.frame.to_local(&-r_in.direction().unit());
+        let wi = lobes.frame.to_local(&scattered.direction().unit());
+        let [p_diffuse, p_specular, p_clearcoat, p_glass] = lobes.probabilities;
+        let white = Color::from(1.0, 1.0, 1.0);
+
+        let mut f = Color::new();
+        let mut pdf = 0.0;
+        if wo.z() > 0.0 && wi.z() > 0.0 {
+            let wm = (wo + wi).unit();
+            let cos_d = wi.dot(&wm);
+            let (fresnel_o, fresnel_i, fresnel_d) = (schlick_weight(wo.z()), schlick_weight(wi.z()), schlick_weight(cos_d));
+
+            // Diffuse, brighter at grazing angles when rough, or flattened towards the
+            // Hanrahan-Krueger approximation of subsurface scattering.
+            let fd90 = 0.5 + 2.0 * cos_d * cos_d * lobes.roughness;
+            let burley = lerp(1.0, fd90, fresnel_o) * lerp(1.0, fd90, fresnel_i);
+            let fss90 = cos_d * cos_d * lobes.roughness;
+            let fss = lerp(1.0, fss90, fresnel_o) * lerp(1.0, fss90, fresnel_i);
+            let subsurface = 1.25 * (fss * (1.0 / (wo.z() + wi.z()) - 0.5) + 0.5);
+            let diffuse = lobes.base_color * (lerp(burley, subsurface, lobes.subsurface) / PI) + lobes.sheen * fresnel_d;
+            f += diffuse * (lobes.diffuse_weight * wi.z());
+            pdf += p_diffuse * wi.z() / PI;
+
+            let fresnel = mix(lobes.specular0, white, fresnel_d);
+            let d_g = lobes.distribution.d(&wm) * lobes.distribution.g(&wo, &wi);
+            f += fresnel * (lobes.specular_weight * d_g / (4.0 * wo.z()));
+            pdf += p_specular * lobes.distribution.pdf(&wo, &wm) / (4.0 * wo.dot(&wm));
+
+            let d = gtr1(wm.z(), lobes.clearcoat_alpha);
+            let g = smith_g1(wo.z(), 0.25) * smith_g1(wi.z(), 0.25);
+            f += white * (0.25 * lobes.clearcoat * d * lerp(0.04, 1.0, fresnel_d) * g / (4.0 * wo.z()));
+            pdf += p_clearcoat * d * wm.z() / (4.0 * wo.dot(&wm));
+        }
+        let mut f = spectrum::upsample(&f);
+
+        // Refraction is tinted by the base colour.
+        if lobes.glass_weight > 0.0 {
+            let tint = if wi.z() < 0.0 { spectrum::upsample(&lobes.base_color) } else { white };
+            f += lobes.glass.eval(r_in, hit_record, scattered) * tint * lobes.glass_weight;
+            pdf += p_glass * lobes.glass.pdf(r_in, hit_record, scattered);
+        }
+
+        (f, pdf)
+    }
+}
+
+impl Material for Principled {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+    ) -> Option<(Color, Ray)> {
+        let lobes = self.lobes(r_in, hit_record);
+        let wo = lobes.frame.to_local(&-r_in.direction().unit());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let [p_diffuse, p_specular, p_clearcoat, _] = lobes.probabilities;
+        let xi = random_double();
+        let direction = if xi < p_diffuse {
+            let wi = Vec3::from(0.0, 0.0, 1.0) + Vec3::random_unit_vector();
+            lobes.frame.local(&if wi.is_zero() { Vec3::from(0.0, 0.0, 1.0) } else { wi })
+        } else if xi < p_diffuse + p_specular {
+            let wm = lobes.distribution.sample_wm(&wo);
+            lobes.frame.local(&microfacet::reflect(&wo, &wm))
+        } else if xi < p_diffuse + p_specular + p_clearcoat {
+            let wm = sample_gtr1(lobes.clearcoat_alpha);
+            lobes.frame.local(&microfacet::reflect(&wo, &wm))
+        } else {
+            lobes.glass.scatter(r_in, hit_record)?.1.direction()
+        };
+
+        let scattered = Ray::from_time(hit_record.point, direction, r_in.time());
+        let (f, pdf) = self.evaluate(&lobes, r_in, hit_record, &scattered);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some((f / pdf, scattered))
+    }
+
+    fn eval(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> Color {
+        let lobes = self.lobes(r_in, hit_record);
+        self.evaluate(&lobes, r_in, hit_record, scattered).0
+    }
+
+    fn pdf(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> f64 {
+        let lobes = self.lobes(r_in, hit_record);
+        self.evaluate(&lobes, r_in, hit_record, scattered).1
+    }
+
+    fn importance_scale(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> f64 {
+        self.lobes(r_in, hit_record).glass.importance_scale(r_in, hit_record, scattered)
+    }
+
+    fn is_specular(&self, _hit_record: &HitRecord) -> bool {
+        false
+    }
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+fn mix(a: Color, b: Color, t: f64) -> Color {
+    a * (1.0 - t) + b * t
+}
+
+// Schlick's (1 - cos)^5, the weight of the grazing reflectance.
+fn schlick_weight(cos_theta: f64) -> f64 {
+    (1.0 - cos_theta).clamp(0.0, 1.0).powi(5)
+}
+
+// Generalized Trowbridge-Reitz with gamma 1, the long tailed distribution of the clearcoat.
+fn gtr1(cos_theta: f64, alpha: f64) -> f64 {
+    let alpha2 = alpha * alpha;
+    (alpha2 - 1.0) / (PI * alpha2.ln() * (1.0 + (alpha2 - 1.0) * cos_theta * cos_theta))
+}
+
+// A normal from 'gtr1' times its cosine.
+fn sample_gtr1(alpha: f64) -> Vec3 {
+    let alpha2 = alpha * alpha;
+    let cos2_theta = ((1.0 - alpha2.powf(1.0 - random_double())) / (1.0 - alpha2)).clamp(0.0, 1.0);
+    let sin_theta = (1.0 - cos2_theta).sqrt();
+    let phi = 2.0 * PI * random_double();
+    Vec3::from(sin_theta * phi.cos(), sin_theta * phi.sin(), cos2_theta.sqrt())
+}
+
+// Separable Smith masking of an isotropic GGX distribution.
+fn smith_g1(cos_theta: f64, alpha: f64) -> f64 {
+    let alpha2 = alpha * alpha;
+    let cos2_theta = cos_theta * cos_theta;
+    2.0 * cos_theta / (cos_theta + (alpha2 + cos2_theta - alpha2 * cos2_theta).sqrt())
+}
@@ -0,0 +1,169 @@
+use std::ops::Mul;
+
+use crate::mat3::Mat3;
+use crate::mat4::Mat4;
+use crate::vec3::Vec3;
+
+// Quaternion w + xi + yj + zk. Unit quaternions represent rotations, and unlike matrices or
+// Euler angles they can be interpolated smoothly (see 'slerp').
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Quat {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Quat::identity()
+    }
+}
+
+#[allow(dead_code)]
+impl Quat {
+    pub fn identity() -> Quat {
+        Quat { w: 1.0, x: 0.0, y: 0.0, z: 0.0 }
+    }
+
+    pub fn from(w: f64, x: f64, y: f64, z: f64) -> Quat {
+        Quat { w, x, y, z }
+    }
+
+    // Counterclockwise rotation by 'degrees' about 'axis', as 'Mat3::rotation'.
+    pub fn from_axis_angle(axis: &Vec3, degrees: f64) -> Quat {
+        let a = axis.unit();
+        let (s, c) = (degrees.to_radians() / 2.0).sin_cos();
+        Quat { w: c, x: a.x() * s, y: a.y() * s, z: a.z() * s }
+    }
+
+    // Rotation about x, then y, then z, as 'Mat3::from_euler'.
+    pub fn from_euler(x: f64, y: f64, z: f64) -> Quat {
+        Quat::from_axis_angle(&Vec3::from(0.0, 0.0, 1.0), z)
+            * Quat::from_axis_angle(&Vec3::from(0.0, 1.0, 0.0), y)
+            * Quat::from_axis_angle(&Vec3::from(1.0, 0.0, 0.0), x)
+    }
+
+    // The rotation 'm', which must be orthonormal with determinant 1 (Shepperd's method).
+    pub fn from_mat3(m: &Mat3) -> Quat {
+        let trace = m.get(0, 0) + m.get(1, 1) + m.get(2, 2);
+        let q = if trace > 0.0 {
+            let s = 2.0 * (trace + 1.0).sqrt();
+            Quat {
+                w: 0.25 * s,
+                x: (m.get(2, 1) - m.get(1, 2)) / s,
+                y: (m.get(0, 2) - m.get(2, 0)) / s,
+                z: (m.get(1, 0) - m.get(0, 1)) / s,
+            }
+        } else if m.get(0, 0) > m.get(1, 1) && m.get(0, 0) > m.get(2, 2) {
+            let s = 2.0 * (1.0 + m.get(0, 0) - m.get(1, 1) - m.get(2, 2)).sqrt();
+            Quat {
+                w: (m.get(2, 1) - m.get(1, 2)) / s,
+                x: 0.25 * s,
+                y: (m.get(0, 1) + m.get(1, 0)) / s,
+                z: (m.get(0, 2) + m.get(2, 0)) / s,
+            }
+        } else if m.get(1, 1) > m.get(2, 2) {
+            let s = 2.0 * (1.0 + m.get(1, 1) - m.get(0, 0) - m.get(2, 2)).sqrt();
+            Quat {
+                w: (m.get(0, 2) - m.get(2, 0)) / s,
+                x: (m.get(0, 1) + m.get(1, 0)) / s,
+                y: 0.25 * s,
+                z: (m.get(1, 2) + m.get(2, 1)) / s,
+            }
+        } else {
+            let s = 2.0 * (1.0 + m.get(2, 2) - m.get(0, 0) - m.get(1, 1)).sqrt();
+            Quat {
+                w: (m.get(1, 0) - m.get(0, 1)) / s,
+                x: (m.get(0, 2) + m.get(2, 0)) / s,
+                y: (m.get(1, 2) + m.get(2, 1)) / s,
+                z: 0.25 * s,
+            }
+        };
+        q.unit()
+    }
+
+    fn vector(&self) -> Vec3 {
+        Vec3::from(self.x, self.y, self.z)
+    }
+
+    pub fn dot(&self, other: &Quat) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn abs(&self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn unit(&self) -> Quat {
+        let length = self.abs();
+        Quat { w: self.w / length, x: self.x / length, y: self.y / length, z: self.z / length }
+    }
+
+    pub fn conjugate(&self) -> Quat {
+        Quat { w: self.w, x: -self.x, y: -self.y, z: -self.z }
+    }
+
+    pub fn inverse(&self) -> Quat {
+        let length2 = self.dot(self);
+        let c = self.conjugate();
+        Quat { w: c.w / length2, x: c.x / length2, y: c.y / length2, z: c.z / length2 }
+    }
+
+    // Rotate 'v' by this unit quaternion, q v q*.
+    pub fn rotate(&self, v: &Vec3) -> Vec3 {
+        let u = self.vector();
+        let t = 2.0 * u.cross(v);
+        *v + self.w * t + u.cross(&t)
+    }
+
+    // Spherical linear interpolation between unit quaternions, along the shorter arc.
+    pub fn slerp(a: &Quat, b: &Quat, t: f64) -> Quat {
+        let mut b = *b;
+        let mut cos_theta = a.dot(&b);
+        if cos_theta < 0.0 {
+            b = Quat { w: -b.w, x: -b.x, y: -b.y, z: -b.z };
+            cos_theta = -cos_theta;
+        }
+
+        // Nearly parallel, where sin(theta) vanishes: interpolate linearly instead.
+        let (wa, wb) = if cos_theta > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let theta = cos_theta.acos();
+            let sin_theta = theta.sin();
+            (((1.0 - t) * theta).sin() / sin_theta, (t * theta).sin() / sin_theta)
+        };
+
+        Quat {
+            w: wa * a.w + wb * b.w,
+            x: wa * a.x + wb * b.x,
+            y: wa * a.y + wb * b.y,
+            z: wa * a.z + wb * b.z,
+        }.unit()
+    }
+
+    pub fn to_mat3(self) -> Mat3 {
+        Mat3::from_columns(
+            &self.rotate(&Vec3::from(1.0, 0.0, 0.0)),
+            &self.rotate(&Vec3::from(0.0, 1.0, 0.0)),
+            &self.rotate(&Vec3::from(0.0, 0.0, 1.0)),
+        )
+    }
+
+    pub fn to_mat4(self) -> Mat4 {
+        Mat4::from_linear(&self.to_mat3(), &Vec3::new())
+    }
+}
+
+impl Mul for Quat {
+    type Output = Quat;
+
+    // Hamilton product: 'a * b' rotates by b first, then a.
+    fn mul(self, other: Quat) -> Quat {
+        let (a, b) = (self.vector(), other.vector());
+        let w = self.w * other.w - a.dot(&b);
+        let v = self.w * b + other.w * a + a.cross(&b);
+        Quat { w, x: v.x(), y: v.y(), z: v.z() }
+    }
+}
@@ -3,15 +3,20 @@ use crate::vec3::Vec3;
 pub struct Ray {
     ori: Vec3,
     dir: Vec3,
+    tm: f64, // Instant the ray exists at, for motion blur
+    medium: Option<Vec3>, // Absorption of the medium it travels through, None if unknown
 }
 
 #[allow(dead_code)]
 impl Ray {
     pub fn new() -> Ray {
-        Ray { ori: Vec3::new(), dir: Vec3::new() }
+        Ray { ori: Vec3::new(), dir: Vec3::new(), tm: 0.0, medium: None }
     }
     pub fn from(ori: Vec3, dir: Vec3) -> Ray {
-        Ray { ori, dir }
+        Ray { ori, dir, tm: 0.0, medium: None }
+    }
+    pub fn from_time(ori: Vec3, dir: Vec3, tm: f64) -> Ray {
+        Ray { ori, dir, tm, medium: None }
     }
 
     pub fn origin(&self) -> Vec3 {
@@ -20,6 +25,17 @@ impl Ray {
     pub fn direction(&self) -> Vec3 {
         self.dir
     }
+    pub fn time(&self) -> f64 {
+        self.tm
+    }
+    pub fn medium(&self) -> Option<Vec3> {
+        self.medium
+    }
+
+    // The same ray travelling through 'medium'. See 'material::medium_towards'.
+    pub fn in_medium(self, medium: Option<Vec3>) -> Ray {
+        Ray { medium, ..self }
+    }
 
     pub fn at(&self, t: f64) -> Vec3 {
         self.ori + self.dir * t
@@ -0,0 +1,39 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use rand::Rng;
+
+// Every random number used while tracing a path ('get_ray', 'scatter', light sampling)
+// is drawn through 'random_double'. By default it comes from 'rand::thread_rng', but an
+// integrator can install its own source on the current thread, e.g. Metropolis light
+// transport replays and mutates the primary sample vector that drives a path.
+
+pub trait Sampler {
+    // Next number in [0, 1).
+    fn next(&mut self) -> f64;
+}
+
+thread_local! {
+    static INSTALLED: RefCell<Option<Rc<RefCell<dyn Sampler>>>> = RefCell::new(None);
+}
+
+pub fn random_double() -> f64 {
+    let installed = INSTALLED.with(|installed| installed.borrow().clone());
+    match installed {
+        Some(sampler) => sampler.borrow_mut().next(),
+        None => rand::thread_rng().gen_range(0.0..1.0),
+    }
+}
+
+pub fn random_range(range: std::ops::Range<f64>) -> f64 {
+    range.start + (range.end - range.start) * random_double()
+}
+
+// Draw the numbers of the current thread from 'sampler' until 'uninstall' is called.
+pub fn install(sampler: Rc<RefCell<dyn Sampler>>) {
+    INSTALLED.with(|installed| *installed.borrow_mut() = Some(sampler));
+}
+
+pub fn uninstall() {
+    INSTALLED.with(|installed| *installed.borrow_mut() = None);
+}
@@ -0,0 +1,342 @@
+use std::cell::Cell;
+use std::sync::OnceLock;
+
+use crate::common::*;
+
+// Spectral rendering. Each camera sample draws three wavelengths, stratified over the visible
+// range, and paths carry the radiance at those wavelengths in the channels of a 'Color'.
+// Materials keep their RGB parameters and 'upsample' them to the path's wavelengths, so the
+// same scene renders in RGB and in spectral mode. Integrators convert each sample back with
+// 'to_rgb', through CIE XYZ.
+//
+// Like the sampler, the wavelengths of the sample being traced live on the current thread.
+// So does the channel an RGB sample is reduced to by 'single_channel'.
+
+pub const WAVELENGTH_MIN: f64 = 380.0; // nm
+pub const WAVELENGTH_MAX: f64 = 720.0;
+
+// Wavelength at which non-dispersive renders evaluate indices of refraction (sodium d line).
+pub const WAVELENGTH_D: f64 = 587.6;
+
+#[derive(Clone, Copy)]
+struct Wavelengths {
+    lambda: [f64; 3],
+    single: bool, // Only the first (hero) wavelength is still carried
+}
+
+thread_local! {
+    static WAVELENGTHS: Cell<Option<Wavelengths>> = const { Cell::new(None) };
+    static CHANNEL: Cell<Option<usize>> = const { Cell::new(None) }; // RGB mode only
+}
+
+// Start tracing a sample: draw its wavelengths, or render in RGB if 'spectral' is false.
+pub fn begin_sample(spectral: bool) {
+    let wavelengths = spectral.then(|| {
+        let range = WAVELENGTH_MAX - WAVELENGTH_MIN;
+        let hero = random_double();
+        let lambda = [0.0, 1.0, 2.0].map(|i| WAVELENGTH_MIN + range * ((hero + i / 3.0) % 1.0));
+        Wavelengths { lambda, single: false }
+    });
+    WAVELENGTHS.with(|current| current.set(wavelengths));
+    CHANNEL.with(|current| current.set(None));
+}
+
+// Wavelength that wavelength dependent scattering follows, None in RGB mode.
+pub fn hero_wavelength() -> Option<f64> {
+    WAVELENGTHS.with(|current| current.get()).map(|wavelengths| wavelengths.lambda[0])
+}
+
+// Keep only the hero wavelength of the current sample, as a path that refracts differently at
+// each wavelength can only carry one. Returns the mask to apply to the path's throughput.
+pub fn terminate_secondary() -> Color {
+    WAVELENGTHS.with(|current| match current.get() {
+        Some(wavelengths) => {
+            current.set(Some(Wavelengths { single: true, ..wavelengths }));
+            Color::from(1.0, 0.0, 0.0)
+        }
+        None => Color::from(1.0, 1.0, 1.0),
+    })
+}
+
+// Keep only one channel of the current sample, for media whose extinction differs by channel,
+// which a path can only sample free flights for one channel at a time. In spectral mode that
+// is the hero wavelength; in RGB mode a channel picked at random, which 'to_rgb' scales back
+// up. Returns the channel and the mask to apply to the path's throughput.
+pub fn single_channel() -> (usize, Color) {
+    if WAVELENGTHS.with(|current| current.get()).is_some() {
+        return (0, terminate_secondary());
+    }
+    let channel = CHANNEL.with(|current| {
+        let channel = current.get().unwrap_or_else(|| ((3.0 * random_double()) as usize).min(2));
+        current.set(Some(channel));
+        channel
+    });
+    (channel, channel_mask(channel))
+}
+
+fn channel_mask(channel: usize) -> Color {
+    [Color::from(1.0, 0.0, 0.0), Color::from(0.0, 1.0, 0.0), Color::from(0.0, 0.0, 1.0)][channel]
+}
+
+// An RGB reflectance, transmittance or emission at the wavelengths of the current sample.
+// Unchanged in RGB mode.
+pub fn upsample(color: &Color) -> Color {
+    match WAVELENGTHS.with(|current| current.get()) {
+        Some(wavelengths) => {
+            let (ordering, corrected) = smits_preimage(color);
+            let [a, b, c] = wavelengths.lambda.map(|lambda| smits(ordering, &corrected, lambda).max(0.0));
+            Color::from(a, b, c)
+        }
+        None => *color,
+    }
+}
+
+// Linear sRGB of the radiance carried at the wavelengths of the current sample.
+// Out of gamut samples have negative components, which average out over a pixel.
+// In RGB mode the colour is unchanged, unless the sample was reduced to a single channel.
+pub fn to_rgb(color: &Color) -> Color {
+    let Some(wavelengths) = WAVELENGTHS.with(|current| current.get()) else {
+        return match CHANNEL.with(|current| current.get()) {
+            Some(channel) => 3.0 * color[channel] * channel_mask(channel),
+            None => *color,
+        };
+    };
+
+    // Each wavelength is drawn with density 1 / range.
+    let range = WAVELENGTH_MAX - WAVELENGTH_MIN;
+    let (count, weight) = if wavelengths.single { (1, 1.0) } else { (3, 1.0 / 3.0) };
+    let mut xyz = Vec3::new();
+    for i in 0..count {
+        xyz += color[i] * cie_xyz(wavelengths.lambda[i]);
+    }
+    xyz_to_rgb(&(xyz * (weight * range / CIE_Y_INTEGRAL)))
+}
+
+// A reflectance or transmittance given as a function of wavelength (nm): its values at the
+// wavelengths of the current sample, or in RGB mode its colour under equal energy white.
+pub fn from_fn<F: Fn(f64) -> f64>(f: F) -> Color {
+    if let Some(wavelengths) = WAVELENGTHS.with(|current| current.get()) {
+        let [a, b, c] = wavelengths.lambda.map(f);
+        return Color::from(a, b, c);
+    }
+
+    // Midpoint rule, normalized so that a constant function keeps its luminance.
+    const BINS: usize = 40;
+    let step = (WAVELENGTH_MAX - WAVELENGTH_MIN) / BINS as f64;
+    let mut xyz = Vec3::new();
+    let mut y = 0.0;
+    for i in 0..BINS {
+        let lambda = WAVELENGTH_MIN + (i as f64 + 0.5) * step;
+        let cie = cie_xyz(lambda);
+        xyz += f(lambda) * cie;
+        y += cie.y();
+    }
+    xyz_to_rgb(&(xyz / y))
+}
+
+// === Colorimetry ===
+
+// Integral of y over the visible range, and X and Z of the equal energy spectrum there.
+const CIE_Y_INTEGRAL: f64 = 106.912;
+const WHITE_E: (f64, f64) = (0.998586, 0.999191);
+
+// CIE 1931 colour matching functions, by the multi-lobe fit of Wyman, Sloan and Shirley (2013).
+fn cie_xyz(lambda: f64) -> Vec3 {
+    let g = |mu: f64, sigma_low: f64, sigma_high: f64| {
+        let t = (lambda - mu) / if lambda < mu { sigma_low } else { sigma_high };
+        (-0.5 * t * t).exp()
+    };
+    Vec3::from(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+// Linear sRGB. The equal energy white of the upsampled spectra is adapted to D65 (XYZ scaling),
+// so RGB white renders white.
+fn xyz_to_rgb(xyz: &Vec3) -> Color {
+    let x = xyz.x() * 0.95047 / WHITE_E.0;
+    let y = xyz.y();
+    let z = xyz.z() * 1.08883 / WHITE_E.1;
+    Color::from(
+        3.2404542 * x - 1.5371385 * y - 0.4985314 * z,
+        -0.9692660 * x + 1.8760108 * y + 0.0415560 * z,
+        0.0556434 * x - 0.2040259 * y + 1.0572252 * z,
+    )
+}
+
+// === RGB to Spectrum ===
+
+// Smits (1999): smooth spectra in 10 bins over the visible range, combined from white and
+// the primaries and secondaries closest to the colour.
+const SMITS_WHITE: [f64; 10] = [1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000];
+const SMITS_CYAN: [f64; 10] = [0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000];
+const SMITS_MAGENTA: [f64; 10] = [1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959];
+const SMITS_YELLOW: [f64; 10] = [0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840];
+const SMITS_RED: [f64; 10] = [0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149];
+const SMITS_GREEN: [f64; 10] = [0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025];
+const SMITS_BLUE: [f64; 10] = [1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496];
+
+// Channels from lowest to highest. Within each ordering the spectrum is linear in the colour.
+const ORDERINGS: [[usize; 3]; 6] = [[0, 1, 2], [0, 2, 1], [1, 0, 2], [1, 2, 0], [2, 0, 1], [2, 1, 0]];
+
+// The tables were fitted to other colour matching functions than these, so on their own
+// saturated colours come back up to 9% off (pure red renders 1.09, 0.04, 0). Each ordering
+// gets the inverse of its round trip from RGB to spectrum and back, which is exact wherever
+// the spectrum stays positive, that is all but fully saturated primaries and secondaries.
+static ROUND_TRIP_INVERSES: OnceLock<[Mat3; 6]> = OnceLock::new();
+
+// The colour the spectrum has to be built from to come back as 'color', and its ordering.
+// The round trip is continuous and close to the identity, so exactly one ordering holds its
+// own preimage.
+fn smits_preimage(color: &Color) -> ([usize; 3], Color) {
+    let inverses = ROUND_TRIP_INVERSES.get_or_init(|| {
+        ORDERINGS.map(|ordering| {
+            let [r, g, b] = [Color::from(1.0, 0.0, 0.0), Color::from(0.0, 1.0, 0.0), Color::from(0.0, 0.0, 1.0)]
+                .map(|basis| round_trip(ordering, &basis));
+            Mat3::from_columns(&r, &g, &b).inverse().unwrap_or_else(Mat3::identity)
+        })
+    });
+
+    let is_ordered = |[lo, mid, hi]: [usize; 3], c: &Color| c[lo] <= c[mid] + 1e-9 && c[mid] <= c[hi] + 1e-9;
+    (0..ORDERINGS.len())
+        .map(|k| (ORDERINGS[k], inverses[k] * *color))
+        .find(|(ordering, corrected)| is_ordered(*ordering, corrected))
+        .unwrap_or_else(|| {
+            let ordering = ORDERINGS.iter().copied().find(|ordering| is_ordered(*ordering, color)).unwrap_or(ORDERINGS[0]);
+            (ordering, *color)
+        })
+}
+
+fn smits([lo, mid, hi]: [usize; 3], color: &Color, lambda: f64) -> f64 {
+    // Linear between the bin centres.
+    let x = (lambda - WAVELENGTH_MIN) / (WAVELENGTH_MAX - WAVELENGTH_MIN) * 10.0 - 0.5;
+    let i = (x.floor().max(0.0) as usize).min(8);
+    let f = (x - i as f64).clamp(0.0, 1.0);
+    let bin = |table: &[f64; 10]| table[i] * (1.0 - f) + table[i + 1] * f;
+
+    // The secondary lacks the lowest channel, the primary is the highest.
+    let secondary = [&SMITS_CYAN, &SMITS_MAGENTA, &SMITS_YELLOW][lo];
+    let primary = [&SMITS_RED, &SMITS_GREEN, &SMITS_BLUE][hi];
+    color[lo] * bin(&SMITS_WHITE) + (color[mid] - color[lo]) * bin(secondary) + (color[hi] - color[mid]) * bin(primary)
+}
+
+// Expected 'to_rgb' of the spectrum 'smits' builds for 'color' with 'ordering'.
+fn round_trip(ordering: [usize; 3], color: &Color) -> Color {
+    const STEPS: usize = 680;
+    let step = (WAVELENGTH_MAX - WAVELENGTH_MIN) / STEPS as f64;
+    let mut xyz = Vec3::new();
+    for i in 0..STEPS {
+        let lambda = WAVELENGTH_MIN + (i as f64 + 0.5) * step;
+        xyz += smits(ordering, color, lambda) * cie_xyz(lambda);
+    }
+    xyz_to_rgb(&(xyz * (step / CIE_Y_INTEGRAL)))
+}
+
+// === Dispersion ===
+
+// Index of refraction by wavelength. Coefficients take wavelengths in micrometres.
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub enum Ior {
+    Constant(f64),
+    Cauchy { a: f64, b: f64 },              // n = a + b / lambda^2
+    Sellmeier { b: [f64; 3], c: [f64; 3] }, // n^2 = 1 + sum of b lambda^2 / (lambda^2 - c)
+}
+
+#[allow(dead_code)]
+impl Ior {
+    // Borosilicate crown glass (Schott N-BK7).
+    pub fn bk7() -> Ior {
+        Ior::Sellmeier { b: [1.03961212, 0.231792344, 1.01046945], c: [0.00600069867, 0.0200179144, 103.560653] }
+    }
+
+    // Dense flint glass (Schott N-SF11), which disperses strongly.
+    pub fn flint() -> Ior {
+        Ior::Sellmeier { b: [1.73759695, 0.313747346, 1.89878101], c: [0.013188707, 0.0623068142, 155.23629] }
+    }
+
+    pub fn fused_silica() -> Ior {
+        Ior::Sellmeier { b: [0.6961663, 0.4079426, 0.8974794], c: [0.00467914826, 0.0135120631, 97.9340025] }
+    }
+
+    pub fn diamond() -> Ior {
+        Ior::Sellmeier { b: [0.3306, 4.3356, 0.0], c: [0.030625, 0.011236, 0.0] }
+    }
+
+    // 'lambda' in nm.
+    pub fn at(&self, lambda: f64) -> f64 {
+        let lambda2 = (lambda / 1000.0) * (lambda / 1000.0);
+        match *self {
+            Ior::Constant(n) => n,
+            Ior::Cauchy { a, b } => a + b / lambda2,
+            Ior::Sellmeier { b, c } => {
+                (1.0 + (0..3).map(|i| b[i] * lambda2 / (lambda2 - c[i])).sum::<f64>()).sqrt()
+            }
+        }
+    }
+
+    pub fn is_dispersive(&self) -> bool {
+        !matches!(self, Ior::Constant(_))
+    }
+
+    // The index seen by the path being traced: in spectral mode a dispersive index follows the
+    // hero wavelength, otherwise it is taken at the d line.
+    pub fn current(&self) -> f64 {
+        match hero_wavelength() {
+            Some(lambda) if self.is_dispersive() => self.at(lambda),
+            _ => self.at(WAVELENGTH_D),
+        }
+    }
+
+    // Mask for the throughput of a path refracted with the 'current' index.
+    pub fn mask(&self) -> Color {
+        if self.is_dispersive() { terminate_secondary() } else { Color::from(1.0, 1.0, 1.0) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Average of 'to_rgb(upsample(color))' over hero wavelengths spread evenly over the range.
+    fn round_trip_average(color: &Color) -> Color {
+        const SAMPLES: usize = 3000;
+        let range = WAVELENGTH_MAX - WAVELENGTH_MIN;
+        let mut sum = Color::new();
+        for k in 0..SAMPLES {
+            let hero = (k as f64 + 0.5) / SAMPLES as f64;
+            let lambda = [0.0, 1.0, 2.0].map(|i| WAVELENGTH_MIN + range * ((hero + i / 3.0) % 1.0));
+            WAVELENGTHS.with(|current| current.set(Some(Wavelengths { lambda, single: false })));
+            sum += to_rgb(&upsample(color));
+        }
+        WAVELENGTHS.with(|current| current.set(None));
+        sum / SAMPLES as f64
+    }
+
+    #[test]
+    fn upsampled_colors_convert_back() {
+        // At least one colour for each ordering of the channels.
+        let colors = [
+            Color::from(0.2, 0.5, 0.8),
+            Color::from(0.2, 0.8, 0.5),
+            Color::from(0.5, 0.2, 0.8),
+            Color::from(0.8, 0.2, 0.5),
+            Color::from(0.5, 0.8, 0.2),
+            Color::from(0.8, 0.5, 0.2),
+            Color::from(0.1, 0.6, 0.3),
+            Color::from(0.7, 0.7, 0.7),
+        ];
+        for color in colors.iter() {
+            let rgb = round_trip_average(color);
+            for channel in 0..3 {
+                assert!(
+                    (rgb[channel] - color[channel]).abs() < 1e-3,
+                    "({}, {}, {}) came back as ({}, {}, {})",
+                    color.x(), color.y(), color.z(), rgb.x(), rgb.y(), rgb.z()
+                );
+            }
+        }
+    }
+}
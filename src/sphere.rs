@@ -5,26 +5,57 @@ use crate::hittable::{ Hittable, HitRecord };
 use crate::vec3::Point3;
 use crate::ray::Ray;
 use crate::material::Material;
+use crate::aabb::Aabb;
 
 pub struct Sphere {
     center: Point3,
+    center_vec: Vec3, // Displacement of the center from time 0 to time 1
     radius: f64,
     material: Arc<dyn Material + Send + Sync>,
 }
 
+#[allow(dead_code)]
 impl Sphere {
     pub fn from(
         center: Point3,
         radius: f64,
         material: Arc<dyn Material + Send + Sync>,
     ) -> Sphere {
-        Sphere { center, radius, material }
+        Sphere { center, center_vec: Vec3::new(), radius, material }
+    }
+
+    // A sphere moving linearly from 'center0' at time 0 to 'center1' at time 1.
+    // It rests at 'center0' before and at 'center1' after.
+    pub fn moving(
+        center0: Point3,
+        center1: Point3,
+        radius: f64,
+        material: Arc<dyn Material + Send + Sync>,
+    ) -> Sphere {
+        Sphere { center: center0, center_vec: center1 - center0, radius, material }
+    }
+
+    fn center(&self, time: f64) -> Point3 {
+        self.center + time.clamp(0.0, 1.0) * self.center_vec
+    }
+
+    // Texture coordinates of a point on the unit sphere: u goes around the y axis from x = -1,
+    // v from the bottom (y = -1) to the top.
+    fn uv(p: &Point3) -> (f64, f64) {
+        let theta = (-p.y()).clamp(-1.0, 1.0).acos();
+        let phi = (-p.z()).atan2(p.x()) + PI;
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    fn area(&self) -> f64 {
+        4.0 * PI * self.radius * self.radius
     }
 }
 
 impl Hittable for Sphere {
     fn hit(&self, ray: &Ray, ray_t: &Interval) -> Option<HitRecord> {
-        let oc = ray.origin() - self.center;
+        let center = self.center(ray.time());
+        let oc = ray.origin() - center;
         let a = ray.direction().abs2();
         let half_b = oc.dot(&ray.direction());
         let c = oc.abs2() - self.radius * self.radius;
@@ -47,15 +78,49 @@ impl Hittable for Sphere {
         }
         
         let point = ray.at(t);
-        let outward_normal = (point - self.center) / self.radius;
+        let outward_normal = (point - center) / self.radius;
         let hit_record = HitRecord::from(
             point,
             ray,
             &outward_normal,
             t,
+            Sphere::uv(&outward_normal),
             Arc::clone(&self.material)
         );
 
         Some(hit_record)
     }
+
+    fn bounding_box(&self) -> Aabb {
+        let r = Vec3::from(self.radius, self.radius, self.radius);
+        let box0 = Aabb::from_points(&(self.center(0.0) - r), &(self.center(0.0) + r));
+        let box1 = Aabb::from_points(&(self.center(1.0) - r), &(self.center(1.0) + r));
+        box0.union(&box1)
+    }
+
+    fn sample_surface(&self, time: f64) -> Option<(HitRecord, f64)> {
+        let outward_normal = Vec3::random_unit_vector();
+        let point = self.center(time) + self.radius * outward_normal;
+        let (u, v) = Sphere::uv(&outward_normal);
+        let hit_record = HitRecord {
+            point,
+            normal: outward_normal,
+            front_face: true,
+            t: 0.0,
+            u,
+            v,
+            material: Arc::clone(&self.material),
+        };
+
+        Some((hit_record, 1.0 / self.area()))
+    }
+
+    fn surface_pdf(&self, point: &Point3, _normal: &Vec3, time: f64) -> f64 {
+        let distance = (*point - self.center(time)).abs();
+        if (distance - self.radius.abs()).abs() < 1e-6 * self.radius.abs().max(1.0) {
+            1.0 / self.area()
+        } else {
+            0.0
+        }
+    }
 }
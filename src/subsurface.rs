@@ -0,0 +1,214 @@
+use std::sync::Arc;
+
+use crate::common::*;
+use crate::hittable::{ Hittable, HitRecord };
+use crate::material::{ Material, Dielectric, RoughDielectric, HenyeyGreenstein };
+use crate::aabb::Aabb;
+use crate::spectrum;
+
+// Translucent solids (skin, wax, marble, milk) as a random walk inside a closed hittable.
+// Light refracts in through a dielectric surface, scatters through a homogeneous medium and
+// leaves wherever the walk reaches the surface again. Hits on the surface return the
+// interface material and hits inside the phase function, so every integrator can follow
+// the walk like any other path. Each scattering event counts as a bounce, so the walks need
+// a higher 'max_depth' than surfaces do, the more so the lighter 'color' is.
+//
+// When 'radius' differs by channel, so does how far light gets between events. A walk then
+// follows one channel of the path (see 'spectrum::single_channel'), sampling its free flights
+// with the extinction of that channel.
+
+#[derive(Clone, Copy)]
+pub struct SubsurfaceInfo {
+    pub color: Color,               // Overall reflectance; exact for an index of refraction of 1, darker above
+    pub radius: Color,              // Mean free path inside (world units) by channel, before directions are randomized
+    pub index_of_refraction: f64,
+    pub roughness: f64,             // Of the surface, 0 for a smooth one
+    pub anisotropy: f64,            // Henyey-Greenstein g of the scattering inside
+}
+
+impl Default for SubsurfaceInfo {
+    fn default() -> SubsurfaceInfo {
+        SubsurfaceInfo {
+            color: Color::from(0.8, 0.8, 0.8),
+            radius: Color::from(0.05, 0.05, 0.05),
+            index_of_refraction: 1.4,
+            roughness: 0.0,
+            anisotropy: 0.0,
+        }
+    }
+}
+
+pub struct Subsurface {
+    boundary: Arc<dyn Hittable + Send + Sync>,
+    extinction: Color, // Per unit distance, by channel
+    grey: bool,        // Same extinction in every channel
+    interface: Arc<dyn Material + Send + Sync>,
+    inner_interface: Arc<dyn Material + Send + Sync>, // Reached from inside, at the end of a walk
+    phase_function: Arc<dyn Material + Send + Sync>,
+}
+
+#[allow(dead_code)]
+impl Subsurface {
+    // The material of 'boundary' is replaced by the one of the surface.
+    pub fn from(boundary: Arc<dyn Hittable + Send + Sync>, info: &SubsurfaceInfo) -> Subsurface {
+        let interface: Arc<dyn Material + Send + Sync> = if info.roughness > 0.0 {
+            Arc::new(RoughDielectric::from(info.index_of_refraction, info.roughness))
+        } else {
+            Arc::new(Dielectric::from(info.index_of_refraction))
+        };
+
+        // Forward scattering carries light further per event, so scattering is made more
+        // frequent by 1 / (1 - g) while absorption per distance stays the same, keeping the
+        // appearance of isotropic scattering (similarity theory: the reduced scattering and
+        // absorption coefficients stay those of g = 0).
+        let g = info.anisotropy.clamp(-0.99, 0.99);
+        let albedo = single_scattering_albedo(&info.color);
+        let extinction = |a: f64, radius: f64| (1.0 - g * (1.0 - a)) / (radius.max(1e-6) * (1.0 - g));
+        let similar = |a: f64| a / (1.0 - g * (1.0 - a));
+        let extinction = Color::from(
+            extinction(albedo.x(), info.radius.x()),
+            extinction(albedo.y(), info.radius.y()),
+            extinction(albedo.z(), info.radius.z()),
+        );
+        let phase_function = Arc::new(HenyeyGreenstein::from(&Color::from(similar(albedo.x()), similar(albedo.y()), similar(albedo.z())), g));
+
+        // Only walks with coloured extinction need to mask the channels they do not follow.
+        let grey = extinction.x() == extinction.y() && extinction.y() == extinction.z();
+        let (inner_interface, phase_function): (Arc<dyn Material + Send + Sync>, Arc<dyn Material + Send + Sync>) = if grey {
+            (Arc::clone(&interface), phase_function)
+        } else {
+            (Arc::new(SingleChannel { material: Arc::clone(&interface) }), Arc::new(SingleChannel { material: phase_function }))
+        };
+        Subsurface {
+            boundary,
+            extinction,
+            grey,
+            interface,
+            inner_interface,
+            phase_function,
+        }
+    }
+
+    // Extinction with which free flights are sampled, for the channel the path is reduced to
+    // if it differs by channel.
+    fn sampled_extinction(&self) -> f64 {
+        if self.grey {
+            return self.extinction.x();
+        }
+        let (channel, _) = spectrum::single_channel();
+        spectrum::upsample(&self.extinction)[channel]
+    }
+}
+
+// Albedo of each scattering event that makes a semi-infinite slab reflect 'color' overall
+// (Chiang et al. 2016, "Practical and Controllable Subsurface Scattering for Production Path
+// Tracing"). Light scatters many times before it leaves, so the albedo has to be much higher.
+fn single_scattering_albedo(color: &Color) -> Color {
+    let invert = |a: f64| {
+        let a = a.clamp(0.0, 1.0);
+        1.0 - (a * (-5.09406 + a * (2.61188 - a * 4.31805))).exp()
+    };
+    Color::from(invert(color.x()), invert(color.y()), invert(color.z()))
+}
+
+impl Hittable for Subsurface {
+    fn hit(&self, ray: &Ray, ray_t: &Interval) -> Option<HitRecord> {
+        // The next surface hit tells which side the ray is on, even for concave boundaries.
+        let surface = self.boundary.hit(ray, &Interval::from(ray_t.min, INFINITY))?;
+        let mut interface = &self.interface;
+        if !surface.front_face {
+            let ray_length = ray.direction().abs();
+            let hit_distance = -(1.0 - random_double()).ln() / self.sampled_extinction();
+            let t = ray_t.min.max(0.0) + hit_distance / ray_length;
+            if t < surface.t.min(ray_t.max) {
+                return Some(HitRecord {
+                    point: ray.at(t),
+                    normal: Vec3::from(1.0, 0.0, 0.0), // Arbitrary, see 'Material::is_volumetric'
+                    front_face: true,
+                    t,
+                    u: 0.0,
+                    v: 0.0,
+                    material: Arc::clone(&self.phase_function),
+                });
+            }
+            interface = &self.inner_interface;
+        }
+
+        if surface.t >= ray_t.max {
+            return None;
+        }
+        Some(HitRecord { material: Arc::clone(interface), ..surface })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+
+    // The surface blocks light (it can only be crossed by refraction), while between two points
+    // inside the medium attenuates it.
+    fn transmittance(&self, ray: &Ray, ray_t: &Interval) -> Color {
+        match self.boundary.hit(ray, &Interval::from(ray_t.min, INFINITY)) {
+            Some(surface) if surface.t < ray_t.max => Color::new(),
+            Some(surface) if !surface.front_face => {
+                let extinction = if self.grey { self.extinction } else { spectrum::upsample(&self.extinction) };
+                let distance = (ray_t.max - ray_t.min.max(0.0)) * ray.direction().abs();
+                Color::from((-extinction.x() * distance).exp(), (-extinction.y() * distance).exp(), (-extinction.z() * distance).exp())
+            }
+            _ => Color::from(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+// === Single Channel ===
+
+// A material reached by a walk with coloured extinction. It masks the channels that the free
+// flights leading to it were not sampled for.
+struct SingleChannel {
+    material: Arc<dyn Material + Send + Sync>,
+}
+
+impl Material for SingleChannel {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+    ) -> Option<(Color, Ray)> {
+        let (attenuation, scattered) = self.material.scatter(r_in, hit_record)?;
+        Some((spectrum::single_channel().1 * attenuation, scattered))
+    }
+
+    fn eval(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> Color {
+        spectrum::single_channel().1 * self.material.eval(r_in, hit_record, scattered)
+    }
+
+    fn pdf(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> f64 {
+        self.material.pdf(r_in, hit_record, scattered)
+    }
+
+    fn importance_scale(
+        &self,
+        r_in: &Ray,
+        hit_record: &HitRecord,
+        scattered: &Ray,
+    ) -> f64 {
+        self.material.importance_scale(r_in, hit_record, scattered)
+    }
+
+    fn is_volumetric(&self) -> bool {
+        self.material.is_volumetric()
+    }
+
+    fn is_specular(&self, hit_record: &HitRecord) -> bool {
+        self.material.is_specular(hit_record)
+    }
+}
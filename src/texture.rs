@@ -0,0 +1,127 @@
+use std::sync::Arc;
+
+use image::RgbImage;
+
+use crate::common::*;
+use crate::perlin::Perlin;
+
+// A colour that varies over a surface, looked up by the texture coordinates of a hit record
+// or by the hit point itself (solid textures).
+pub trait Texture {
+    fn value(&self, u: f64, v: f64, point: &Point3) -> Color;
+}
+
+// Shorthands for untextured parameters.
+#[allow(dead_code)]
+pub fn solid(color: &Color) -> Arc<dyn Texture + Send + Sync> {
+    Arc::new(SolidColor::from(color))
+}
+
+#[allow(dead_code)]
+pub fn constant(value: f64) -> Arc<dyn Texture + Send + Sync> {
+    Arc::new(SolidColor::from(&Color::from(value, value, value)))
+}
+
+// === Solid Color ===
+
+pub struct SolidColor {
+    albedo: Color,
+}
+
+impl SolidColor {
+    pub fn from(color: &Color) -> SolidColor {
+        SolidColor {
+            albedo: *color,
+        }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _point: &Point3) -> Color {
+        self.albedo
+    }
+}
+
+// === Checker ===
+
+// Alternating 3D cells of 'scale' world units.
+pub struct CheckerTexture {
+    inv_scale: f64,
+    even: Arc<dyn Texture + Send + Sync>,
+    odd: Arc<dyn Texture + Send + Sync>,
+}
+
+#[allow(dead_code)]
+impl CheckerTexture {
+    pub fn from(scale: f64, even: Arc<dyn Texture + Send + Sync>, odd: Arc<dyn Texture + Send + Sync>) -> CheckerTexture {
+        CheckerTexture {
+            inv_scale: 1.0 / scale,
+            even,
+            odd,
+        }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, point: &Point3) -> Color {
+        let cell = |x: f64| (self.inv_scale * x).floor() as i64;
+        if (cell(point.x()) + cell(point.y()) + cell(point.z())) % 2 == 0 {
+            self.even.value(u, v, point)
+        } else {
+            self.odd.value(u, v, point)
+        }
+    }
+}
+
+// === Image ===
+
+// An image stretched over the texture coordinates, v = 0 at the bottom. Pixels are decoded
+// with the same gamma 2 that 'transform_color' encodes with.
+pub struct ImageTexture {
+    image: RgbImage,
+}
+
+#[allow(dead_code)]
+impl ImageTexture {
+    pub fn load(path: &str) -> image::ImageResult<ImageTexture> {
+        Ok(ImageTexture { image: image::open(path)?.to_rgb8() })
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _point: &Point3) -> Color {
+        if self.image.width() == 0 || self.image.height() == 0 {
+            return Color::from(0.0, 1.0, 1.0); // Cyan marks a missing texture
+        }
+        let i = ((u.clamp(0.0, 1.0) * self.image.width() as f64) as u32).min(self.image.width() - 1);
+        let j = (((1.0 - v.clamp(0.0, 1.0)) * self.image.height() as f64) as u32).min(self.image.height() - 1);
+        let pixel = self.image.get_pixel(i, j);
+        let decode = |c: u8| (c as f64 / 255.0).powi(2);
+        Color::from(decode(pixel[0]), decode(pixel[1]), decode(pixel[2]))
+    }
+}
+
+// === Noise ===
+
+// Marble-like veins of Perlin turbulence along z (book 2, chapter 5).
+pub struct NoiseTexture {
+    noise: Perlin,
+    scale: f64,
+}
+
+#[allow(dead_code)]
+impl NoiseTexture {
+    pub fn from(scale: f64) -> NoiseTexture {
+        NoiseTexture {
+            noise: Perlin::new(),
+            scale,
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, point: &Point3) -> Color {
+        let gray = 0.5 * (1.0 + (self.scale * point.z() + 10.0 * self.noise.turb(point, 7)).sin());
+        Color::from(gray, gray, gray)
+    }
+}
@@ -0,0 +1,239 @@
+use std::sync::Arc;
+
+use crate::common::*;
+use crate::aabb::Aabb;
+use crate::hittable::{ Hittable, HitRecord };
+
+// Places an object in the world with an affine transform, fixed or animated over time.
+// The object itself stays in its own object space and may be shared: many transforms can
+// instance the same 'Arc<dyn Hittable>'.
+//
+// Rays are moved into object space without renormalizing the direction, so the object
+// reports the same t as the world ray would.
+pub struct Transform {
+    object: Arc<dyn Hittable + Send + Sync>,
+    placement: Placement,    // At every time, unless 'keyframes' is given
+    keyframes: Vec<Keyframe>, // Sorted by time
+    bbox: Aabb,
+}
+
+// One pose of an animated transform. Translation, rotation and scale are interpolated
+// separately, so a rotating object stays rigid instead of shrinking like a blend of matrices.
+#[derive(Clone, Copy)]
+pub struct Keyframe {
+    pub time: f64,
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+#[allow(dead_code)]
+impl Keyframe {
+    pub fn from(time: f64, translation: Vec3, rotation: Quat, scale: Vec3) -> Keyframe {
+        Keyframe { time, translation, rotation, scale }
+    }
+
+    // Decompose 'matrix' into translation * rotation * scale. Shear is not supported.
+    pub fn from_matrix(time: f64, matrix: &Mat4) -> Keyframe {
+        let linear = matrix.linear();
+        let mut columns = [linear.column(0), linear.column(1), linear.column(2)];
+        let mut scale = Vec3::from(columns[0].abs(), columns[1].abs(), columns[2].abs());
+        if linear.determinant() < 0.0 {
+            // A mirror: fold the reflection into the scale so the rest is a proper rotation.
+            scale = Vec3::from(-scale.x(), scale.y(), scale.z());
+        }
+        for (i, column) in columns.iter_mut().enumerate() {
+            *column /= scale[i];
+        }
+        let rotation = Quat::from_mat3(&Mat3::from_columns(&columns[0], &columns[1], &columns[2]));
+
+        Keyframe { time, translation: matrix.offset(), rotation, scale }
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::translation(&self.translation) * self.rotation.to_mat4() * Mat4::scaling(&self.scale)
+    }
+
+    // The pose a fraction 't' of the way from 'a' to 'b'.
+    pub fn interpolate(a: &Keyframe, b: &Keyframe, t: f64) -> Keyframe {
+        Keyframe {
+            time: a.time + t * (b.time - a.time),
+            translation: a.translation + t * (b.translation - a.translation),
+            rotation: Quat::slerp(&a.rotation, &b.rotation, t),
+            scale: a.scale + t * (b.scale - a.scale),
+        }
+    }
+}
+
+// A transform at one instant, with the derived matrices needed to apply it.
+#[derive(Clone, Copy)]
+struct Placement {
+    matrix: Mat4,        // Object to world
+    inverse: Mat4,       // World to object
+    normal_matrix: Mat3, // Object to world for normals
+}
+
+impl Placement {
+    fn from(matrix: Mat4) -> Placement {
+        let inverse = matrix.inverse().expect("Transform matrix must be invertible");
+        Placement {
+            matrix,
+            inverse,
+            normal_matrix: inverse.linear().transpose(),
+        }
+    }
+
+    fn object_ray(&self, ray: &Ray) -> Ray {
+        Ray::from_time(
+            self.inverse.transform_point(&ray.origin()),
+            self.inverse.transform_vector(&ray.direction()),
+            ray.time(),
+        )
+    }
+
+    fn world_record(&self, mut hit_record: HitRecord) -> HitRecord {
+        hit_record.point = self.matrix.transform_point(&hit_record.point);
+        hit_record.normal = (self.normal_matrix * hit_record.normal).unit();
+        hit_record
+    }
+
+    // Ratio of world to object area at a point where the object space surface has unit 'normal'.
+    fn area_scale(&self, normal: &Vec3) -> f64 {
+        self.matrix.linear().determinant().abs() * (self.normal_matrix * *normal).abs()
+    }
+
+    fn bounding_box(&self, object_box: &Aabb) -> Aabb {
+        if object_box.is_empty() || !object_box.is_finite() {
+            return *object_box;
+        }
+        object_box
+            .corners()
+            .iter()
+            .map(|corner| self.matrix.transform_point(corner))
+            .fold(Aabb::new(), |bbox, point| bbox.union(&Aabb::from_points(&point, &point)))
+    }
+}
+
+// Poses sampled per keyframe interval when bounding an animated transform.
+const BOUNDING_STEPS: usize = 32;
+
+#[allow(dead_code)]
+impl Transform {
+    // 'matrix' maps object space to world space and must be invertible.
+    pub fn from(object: Arc<dyn Hittable + Send + Sync>, matrix: Mat4) -> Transform {
+        let placement = Placement::from(matrix);
+        let bbox = placement.bounding_box(&object.bounding_box());
+        Transform {
+            object,
+            placement,
+            keyframes: Vec::new(),
+            bbox,
+        }
+    }
+
+    // Move through 'keyframes' as the ray time advances, holding the first and last pose
+    // before and after them. Several keyframes allow turns of more than half a revolution,
+    // which interpolating two poses would take the short way round.
+    pub fn animated(object: Arc<dyn Hittable + Send + Sync>, mut keyframes: Vec<Keyframe>) -> Transform {
+        assert!(!keyframes.is_empty(), "An animated transform needs at least one keyframe");
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        // The box swept between keyframes, from poses sampled along the way. Between samples,
+        // points move along arcs of at most pi / BOUNDING_STEPS, which bulge out of the chord
+        // by no more than radius * (1 - cos(pi / (2 * BOUNDING_STEPS))).
+        let object_box = object.bounding_box();
+        let mut bbox = Placement::from(keyframes[0].matrix()).bounding_box(&object_box);
+        for pair in keyframes.windows(2) {
+            for step in 1..=BOUNDING_STEPS {
+                let pose = Keyframe::interpolate(&pair[0], &pair[1], step as f64 / BOUNDING_STEPS as f64);
+                bbox = bbox.union(&Placement::from(pose.matrix()).bounding_box(&object_box));
+            }
+        }
+        if keyframes.len() > 1 && !bbox.is_empty() && bbox.is_finite() {
+            let object_radius = object_box.corners().iter().map(|corner| corner.abs()).fold(0.0, f64::max);
+            let max_scale = keyframes
+                .iter()
+                .map(|k| k.scale.x().abs().max(k.scale.y().abs()).max(k.scale.z().abs()))
+                .fold(0.0, f64::max);
+            let bulge = object_radius * max_scale * (1.0 - (PI / (2.0 * BOUNDING_STEPS as f64)).cos());
+            let grow = |slab: &Interval| Interval::from(slab.min - bulge, slab.max + bulge);
+            bbox = Aabb::from(grow(&bbox.x), grow(&bbox.y), grow(&bbox.z));
+        }
+
+        Transform {
+            object,
+            placement: Placement::from(keyframes[0].matrix()),
+            keyframes,
+            bbox,
+        }
+    }
+
+    pub fn translate(object: Arc<dyn Hittable + Send + Sync>, offset: &Vec3) -> Transform {
+        Transform::from(object, Mat4::translation(offset))
+    }
+
+    pub fn rotate(object: Arc<dyn Hittable + Send + Sync>, axis: &Vec3, degrees: f64) -> Transform {
+        Transform::from(object, Mat4::rotation(axis, degrees))
+    }
+
+    pub fn scale(object: Arc<dyn Hittable + Send + Sync>, factors: &Vec3) -> Transform {
+        Transform::from(object, Mat4::scaling(factors))
+    }
+
+    // Object to world matrix at 'time'.
+    pub fn matrix(&self, time: f64) -> Mat4 {
+        self.placement(time).matrix
+    }
+
+    fn placement(&self, time: f64) -> Placement {
+        if self.keyframes.len() < 2 {
+            return self.placement;
+        }
+        let first = &self.keyframes[0];
+        let last = &self.keyframes[self.keyframes.len() - 1];
+        if time <= first.time {
+            return Placement::from(first.matrix());
+        }
+        if time >= last.time {
+            return Placement::from(last.matrix());
+        }
+
+        let next = self.keyframes.partition_point(|k| k.time <= time);
+        let (a, b) = (&self.keyframes[next - 1], &self.keyframes[next]);
+        let t = (time - a.time) / (b.time - a.time);
+        Placement::from(Keyframe::interpolate(a, b, t).matrix())
+    }
+}
+
+impl Hittable for Transform {
+    fn hit(&self, ray: &Ray, ray_t: &Interval) -> Option<HitRecord> {
+        // The normal matrix preserves the sign of dot(direction, normal), and so 'front_face'.
+        let placement = self.placement(ray.time());
+        let hit_record = self.object.hit(&placement.object_ray(ray), ray_t)?;
+        Some(placement.world_record(hit_record))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn sample_surface(&self, time: f64) -> Option<(HitRecord, f64)> {
+        let placement = self.placement(time);
+        let (hit_record, pdf) = self.object.sample_surface(time)?;
+        let area_scale = placement.area_scale(&hit_record.normal);
+        Some((placement.world_record(hit_record), pdf / area_scale))
+    }
+
+    fn surface_pdf(&self, point: &Point3, normal: &Vec3, time: f64) -> f64 {
+        // Normals map back to object space with the transpose of the linear part.
+        let placement = self.placement(time);
+        let object_normal = (placement.matrix.linear().transpose() * *normal).unit();
+        let object_point = placement.inverse.transform_point(point);
+        self.object.surface_pdf(&object_point, &object_normal, time) / placement.area_scale(&object_normal)
+    }
+
+    fn transmittance(&self, ray: &Ray, ray_t: &Interval) -> Color {
+        let placement = self.placement(ray.time());
+        self.object.transmittance(&placement.object_ray(ray), ray_t)
+    }
+}
@@ -1,5 +1,3 @@
-use rand::Rng;
-
 use crate::common::*;
 
 #[derive(Copy, Clone)]
@@ -11,6 +9,12 @@ pub struct Vec3 {
 
 pub type Point3 = Vec3;
 
+impl Default for Vec3 {
+    fn default() -> Self {
+        Vec3::new()
+    }
+}
+
 #[allow(dead_code)]
 impl Vec3 {
     pub fn new() -> Vec3 {
@@ -20,7 +24,7 @@ impl Vec3 {
         Vec3 { x, y, z }
     }
     pub fn random(range: std::ops::Range<f64>) -> Vec3 {
-        let rd = || rand::thread_rng().gen_range(range.clone());
+        let rd = || random_range(range.clone());
         Vec3 { x: rd(), y: rd(), z: rd() }
     }
     pub fn random_in_unit_sphere() -> Vec3 {
@@ -92,10 +96,10 @@ impl Vec3 {
         self.abs2().sqrt()
     }
     pub fn abs2(&self) -> f64 {
-        self.dot(&self)
+        self.dot(self)
     }
     pub fn unit(&self) -> Vec3 {
-        self.clone() / self.abs()
+        *self / self.abs()
     }
 
     pub fn is_zero(&self) -> bool {
@@ -120,7 +124,7 @@ use std::ops::Sub;
 impl Sub for Vec3 {
     type Output = Self;
 
-    fn sub(self: Self, other: Self) -> Self {
+    fn sub(self, other: Self) -> Self {
         Self {
             x: self.x - other.x,
             y: self.y - other.y,
@@ -133,7 +137,7 @@ use std::ops::Mul;
 impl Mul for Vec3 {
     type Output = Self;
 
-    fn mul(self: Self, other: Self) -> Self {
+    fn mul(self, other: Self) -> Self {
         Self {
             x: self.x * other.x, 
             y: self.y * other.y, 
@@ -168,7 +172,7 @@ use std::ops::Div;
 impl Div for Vec3 {
     type Output = Self;
 
-    fn div(self: Self, other: Self) -> Self {
+    fn div(self, other: Self) -> Self {
         Self {
             x: self.x / other.x, 
             y: self.y / other.y, 